@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use miniscript::bitcoin::{
+    absolute::LockTime,
+    consensus::{deserialize, encode::serialize_hex},
+    hashes::{hex::FromHex, Hash},
+    transaction::Version,
+    Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use serde_json::Value;
+use simple_electrum_client::electrum::{
+    method::Method, request::Request, response::*, script::ToScriptHash, slices::SliceDecoder,
+    types::Id,
+};
+
+const HEADER: &str = "00000020835fdbdeeadd23463fad98b4e21aaa8519afde89eecd0eb224001317421cbb5f5e636df02303e51280b586bc596ee9326bc849bbb5993e121a8cab7e6b60e8ab593fe166ffff7f2000000000";
+
+// The parser before the single-pass dispatch, every candidate type is tried
+// in turn & batch elements are serialized back to strings.
+fn legacy_parse(raw: &str, index: &HashMap<Id, Request>) -> Option<Response> {
+    if let Ok(e) = serde_json::from_str::<ErrorResponse>(raw) {
+        return Some(Response::Error(e));
+    }
+    if let Ok(n) = serde_json::from_str::<BatchHeaderNotif>(raw) {
+        return Some(Response::BatchHeaderNotif(n));
+    }
+    if let Ok(n) = serde_json::from_str::<SHNotification>(raw) {
+        return Some(Response::SHNotification(n));
+    }
+    if let Ok(n) = serde_json::from_str::<OutpointNotification>(raw) {
+        return Some(Response::OutpointNotification(n));
+    }
+    let rr: RawResponse = serde_json::from_str(raw).ok()?;
+    match index.get(&rr.id)?.method {
+        Method::ScriptHashGetHistory => serde_json::from_str(raw).ok().map(Response::SHGetHistory),
+        Method::ScriptHashGetBalance => serde_json::from_str(raw).ok().map(Response::SHGetBalance),
+        Method::BlockHeaders => serde_json::from_str(raw).ok().map(Response::Headers),
+        _ => None,
+    }
+}
+
+fn legacy_batch(raw: &str, index: &HashMap<Id, Request>) -> Option<Vec<Response>> {
+    let batch: Vec<Value> = serde_json::from_str(raw).ok()?;
+    batch
+        .iter()
+        .map(|v| legacy_parse(&serde_json::to_string(v).unwrap(), index))
+        .collect()
+}
+
+fn history(len: usize) -> String {
+    let history: Vec<_> = (0..len)
+        .map(|i| format!(r#"{{"height":{},"tx_hash":"{:064x}"}}"#, i + 100_000, i))
+        .collect();
+    format!(
+        r#"{{"jsonrpc":"2.0","id":0,"result":[{}]}}"#,
+        history.join(",")
+    )
+}
+
+fn headers(count: usize) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","id":0,"result":{{"count":{},"hex":"{}","max":2016}}}}"#,
+        count,
+        HEADER.repeat(count)
+    )
+}
+
+fn balances(count: usize) -> String {
+    let batch: Vec<_> = (0..count)
+        .map(|i| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":{},"result":{{"confirmed":{},"unconfirmed":0}}}}"#,
+                i,
+                i * 1000
+            )
+        })
+        .collect();
+    format!("[{}]", batch.join(","))
+}
+
+fn transactions(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            let tx = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: (0..2)
+                    .map(|vout| TxIn {
+                        previous_output: OutPoint::new(Txid::from_byte_array([i as u8; 32]), vout),
+                        script_sig: ScriptBuf::new(),
+                        sequence: Sequence::MAX,
+                        witness: Witness::from_slice(&[vec![1u8; 72], vec![2u8; 33]]),
+                    })
+                    .collect(),
+                output: (0..2)
+                    .map(|j| TxOut {
+                        value: Amount::from_sat(1000 * j),
+                        script_pubkey: ScriptBuf::from_bytes(vec![0x00; 22]),
+                    })
+                    .collect(),
+            };
+            serialize_hex(&tx)
+        })
+        .collect()
+}
+
+fn index(requests: Vec<Request>) -> HashMap<Id, Request> {
+    requests.into_iter().map(|r| (r.id.clone(), r)).collect()
+}
+
+fn bench(c: &mut Criterion) {
+    let sh = ScriptBuf::new().to_script_hash();
+
+    let raw = history(5_000);
+    let idx = index(vec![Request::sh_get_history(sh)]);
+    assert!(matches!(
+        Response::try_parse(&raw, &idx).unwrap(),
+        Response::SHGetHistory(_)
+    ));
+    let mut group = c.benchmark_group("history");
+    group.bench_function("single_pass", |b| {
+        b.iter(|| Response::try_parse(black_box(&raw), &idx).unwrap())
+    });
+    group.bench_function("legacy", |b| {
+        b.iter(|| legacy_parse(black_box(&raw), &idx).unwrap())
+    });
+    group.finish();
+
+    let raw = headers(2016);
+    let idx = index(vec![Request::headers(0, 2016)]);
+    let mut group = c.benchmark_group("headers");
+    group.bench_function("single_pass", |b| {
+        b.iter(|| Response::try_parse(black_box(&raw), &idx).unwrap())
+    });
+    group.bench_function("legacy", |b| {
+        b.iter(|| legacy_parse(black_box(&raw), &idx).unwrap())
+    });
+    group.finish();
+
+    let raw = balances(500);
+    let idx = index(
+        (0..500)
+            .map(|i| Request::sh_get_balance(sh).id(i))
+            .collect(),
+    );
+    assert_eq!(parse_str_response(&raw, &idx).unwrap().len(), 500);
+    let mut group = c.benchmark_group("batch");
+    group.bench_function("single_pass", |b| {
+        b.iter(|| parse_str_response(black_box(&raw), &idx).unwrap())
+    });
+    group.bench_function("legacy", |b| {
+        b.iter(|| legacy_batch(black_box(&raw), &idx).unwrap())
+    });
+    group.finish();
+
+    let txs = transactions(1_000);
+    let mut decoder = SliceDecoder::new();
+    let mut group = c.benchmark_group("transactions");
+    group.bench_function("slices", |b| {
+        b.iter(|| {
+            let mut value = 0;
+            for tx in &txs {
+                decoder
+                    .tx_outputs(black_box(tx), |_, v, _| value += v.to_sat())
+                    .unwrap();
+            }
+            value
+        })
+    });
+    group.bench_function("transaction", |b| {
+        b.iter(|| {
+            let mut value = 0;
+            for tx in &txs {
+                let bytes = Vec::<u8>::from_hex(black_box(tx)).unwrap();
+                let tx: Transaction = deserialize(&bytes).unwrap();
+                let _ = tx.compute_txid();
+                value += tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+            }
+            value
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,492 @@
+use std::ops::RangeInclusive;
+
+use miniscript::bitcoin::{block::Header as BlockHeader, BlockHash};
+
+use crate::{
+    client::{self, Client},
+    electrum::{
+        self,
+        request::Request,
+        response::{Header, HeaderNotification, Response},
+        types::{HeaderRow, SerializedHeaderRow},
+        verify::{verify_header_chain, HeaderSource},
+    },
+};
+
+/// Headers are downloaded by chunks of one difficulty period.
+pub const CHUNK_SIZE: usize = 2016;
+
+#[derive(Debug)]
+pub enum Error {
+    Client(client::Error),
+    Electrum(electrum::Error),
+    /// The headers starting at this height do not link to the stored chain.
+    Fork(usize),
+    /// The headers start above the next expected height.
+    Gap {
+        expected: usize,
+        got: usize,
+    },
+    GenesisMismatch,
+}
+
+impl From<client::Error> for Error {
+    fn from(value: client::Error) -> Self {
+        Error::Client(value)
+    }
+}
+
+impl From<electrum::Error> for Error {
+    fn from(value: electrum::Error) -> Self {
+        Error::Electrum(value)
+    }
+}
+
+/// Storage backend of the header chain, rows are indexed by height starting
+/// from genesis.
+pub trait HeaderStore {
+    fn len(&self) -> usize;
+    fn get(&self, height: usize) -> Option<SerializedHeaderRow>;
+    fn push(&mut self, row: SerializedHeaderRow);
+    /// Remove the rows at `height` and above.
+    fn truncate(&mut self, height: usize);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    rows: Vec<SerializedHeaderRow>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HeaderStore for MemoryStore {
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn get(&self, height: usize) -> Option<SerializedHeaderRow> {
+        self.rows.get(height).copied()
+    }
+
+    fn push(&mut self, row: SerializedHeaderRow) {
+        self.rows.push(row)
+    }
+
+    fn truncate(&mut self, height: usize) {
+        self.rows.truncate(height)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// Headers have been appended to the chain.
+    Connected(RangeInclusive<usize>),
+    /// The headers above `fork_height` have been rolled back.
+    Reorg {
+        fork_height: usize,
+        disconnected: RangeInclusive<usize>,
+    },
+}
+
+/// A local copy of the server's best header chain.
+#[derive(Debug)]
+pub struct HeaderChain<S: HeaderStore> {
+    store: S,
+}
+
+impl<S: HeaderStore> HeaderChain<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// Height of the tip, `None` if the chain is empty.
+    pub fn height(&self) -> Option<usize> {
+        self.store.len().checked_sub(1)
+    }
+
+    pub fn header(&self, height: usize) -> Option<BlockHeader> {
+        self.store
+            .get(height)
+            .map(|row| HeaderRow::from_db_row(row).header)
+    }
+
+    pub fn block_hash(&self, height: usize) -> Option<BlockHash> {
+        self.header(height).map(|h| h.block_hash())
+    }
+
+    pub fn tip(&self) -> Option<BlockHeader> {
+        self.height().and_then(|h| self.header(h))
+    }
+
+    fn rollback(&mut self, fork_height: usize) -> Option<ChainEvent> {
+        let tip = self.height()?;
+        if fork_height >= tip {
+            return None;
+        }
+        self.store.truncate(fork_height + 1);
+        Some(ChainEvent::Reorg {
+            fork_height,
+            disconnected: fork_height + 1..=tip,
+        })
+    }
+
+    /// Connect `headers` at height `start`, stored headers that differ from
+    /// the new ones are rolled back.
+    pub fn connect(
+        &mut self,
+        start: usize,
+        headers: &[BlockHeader],
+    ) -> Result<Vec<ChainEvent>, Error> {
+        let mut events = Vec::new();
+        if headers.is_empty() {
+            return Ok(events);
+        }
+        let len = self.store.len();
+        if start > len {
+            return Err(Error::Gap {
+                expected: len,
+                got: start,
+            });
+        }
+        let prev = start.checked_sub(1).and_then(|h| self.header(h));
+        verify_header_chain(prev.as_ref(), headers).map_err(|e| match e {
+            electrum::Error::HeaderNotChained(0) => Error::Fork(start),
+            e => e.into(),
+        })?;
+
+        // skip the headers we already have
+        let known = headers
+            .iter()
+            .enumerate()
+            .take_while(|(i, h)| self.block_hash(start + i) == Some(h.block_hash()))
+            .count();
+        if known == headers.len() {
+            return Ok(events);
+        }
+        let height = start + known;
+        if height < len {
+            let fork_height = height.checked_sub(1).ok_or(Error::GenesisMismatch)?;
+            events.extend(self.rollback(fork_height));
+        }
+        for header in &headers[known..] {
+            self.store.push(HeaderRow::new(*header).to_db_row());
+        }
+        events.push(ChainEvent::Connected(height..=start + headers.len() - 1));
+        Ok(events)
+    }
+
+    fn fetch(client: &mut Client, start: usize, count: usize) -> Result<Vec<BlockHeader>, Error> {
+        match client.request(Request::headers(start, count))? {
+            Response::Headers(r) => Ok(r.headers.headers()?),
+            Response::Error(e) => Err(client::Error::Rpc(e).into()),
+            _ => Err(client::Error::WrongResponse.into()),
+        }
+    }
+
+    /// Walk back the stored chain to find the last header shared with the
+    /// server, the headers above it are rolled back.
+    fn find_fork(&mut self, client: &mut Client) -> Result<Option<ChainEvent>, Error> {
+        let mut end = self.store.len();
+        while end > 0 {
+            let start = end.saturating_sub(CHUNK_SIZE);
+            let headers = Self::fetch(client, start, end - start)?;
+            for (i, header) in headers.iter().enumerate().rev() {
+                let height = start + i;
+                if self.block_hash(height) == Some(header.block_hash()) {
+                    return Ok(self.rollback(height));
+                }
+            }
+            end = start;
+        }
+        Err(Error::GenesisMismatch)
+    }
+
+    /// Download the headers from the stored tip up to the server tip.
+    pub fn sync(&mut self, client: &mut Client) -> Result<Vec<ChainEvent>, Error> {
+        let mut events = Vec::new();
+        loop {
+            let start = self.store.len();
+            // keep requests aligned on chunks
+            let count = CHUNK_SIZE - start % CHUNK_SIZE;
+            let headers = Self::fetch(client, start, count)?;
+            match self.connect(start, &headers) {
+                Ok(e) => events.extend(e),
+                Err(Error::Fork(height)) => match self.find_fork(client)? {
+                    Some(reorg) => {
+                        events.push(reorg);
+                        continue;
+                    }
+                    // the server sent a chain that does not link to itself
+                    None => return Err(Error::Fork(height)),
+                },
+                Err(e) => return Err(e),
+            }
+            if headers.len() < count {
+                return Ok(events);
+            }
+        }
+    }
+
+    /// Subscribe to new tips and sync the chain up to the current one.
+    pub fn subscribe(&mut self, client: &mut Client) -> Result<Vec<ChainEvent>, Error> {
+        match client.request(Request::subscribe_headers())? {
+            Response::HeaderNotif(HeaderNotification::Single(n)) => {
+                self.handle_header(client, &n.header)
+            }
+            Response::Error(e) => Err(client::Error::Rpc(e).into()),
+            _ => Err(client::Error::WrongResponse.into()),
+        }
+    }
+
+    /// Process a `blockchain.headers.subscribe` notification, any other
+    /// response is ignored.
+    pub fn handle_notification(
+        &mut self,
+        client: &mut Client,
+        response: &Response,
+    ) -> Result<Vec<ChainEvent>, Error> {
+        let headers = match response {
+            Response::HeaderNotif(HeaderNotification::Single(n)) => std::slice::from_ref(&n.header),
+            Response::HeaderNotif(HeaderNotification::Batch(n)) | Response::BatchHeaderNotif(n) => {
+                &n.headers
+            }
+            _ => return Ok(Vec::new()),
+        };
+        let mut events = Vec::new();
+        for header in headers {
+            events.extend(self.handle_header(client, header)?);
+        }
+        Ok(events)
+    }
+
+    fn handle_header(
+        &mut self,
+        client: &mut Client,
+        header: &Header,
+    ) -> Result<Vec<ChainEvent>, Error> {
+        match self.connect(header.height, &[header.header()?]) {
+            Ok(events) => Ok(events),
+            // we missed some blocks
+            Err(Error::Gap { .. }) => self.sync(client),
+            // the new tip is on another branch
+            Err(Error::Fork(_)) => {
+                let mut events: Vec<_> = self.find_fork(client)?.into_iter().collect();
+                events.extend(self.sync(client)?);
+                Ok(events)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<S: HeaderStore> HeaderSource for HeaderChain<S> {
+    fn header_at(&self, height: usize) -> Option<BlockHeader> {
+        self.header(height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miniscript::bitcoin::{block::Version, hashes::Hash, CompactTarget, TxMerkleNode};
+
+    use super::*;
+    use crate::{
+        electrum::{response::SingleHeaderNotif, types::Id},
+        raw_client::Client as RawClient,
+    };
+
+    fn mine(prev: BlockHash, time: u32) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: Version::TWO,
+            prev_blockhash: prev,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    // `time` is used to make branches differ
+    fn branch(prev: Option<&BlockHeader>, count: usize, time: u32) -> Vec<BlockHeader> {
+        let mut prev = prev
+            .map(|h| h.block_hash())
+            .unwrap_or(BlockHash::all_zeros());
+        (0..count)
+            .map(|i| {
+                let header = mine(prev, time + i as u32);
+                prev = header.block_hash();
+                header
+            })
+            .collect()
+    }
+
+    fn chain(headers: &[BlockHeader]) -> HeaderChain<MemoryStore> {
+        let mut chain = HeaderChain::new(MemoryStore::new());
+        chain.connect(0, headers).unwrap();
+        chain
+    }
+
+    #[test]
+    fn connect() {
+        let headers = branch(None, 10, 0);
+        let mut chain = HeaderChain::new(MemoryStore::new());
+        assert_eq!(chain.height(), None);
+
+        let events = chain.connect(0, &headers[..5]).unwrap();
+        assert_eq!(events, vec![ChainEvent::Connected(0..=4)]);
+        let events = chain.connect(5, &headers[5..]).unwrap();
+        assert_eq!(events, vec![ChainEvent::Connected(5..=9)]);
+        assert_eq!(chain.height(), Some(9));
+        assert_eq!(chain.tip(), Some(headers[9]));
+        assert_eq!(chain.header(3), Some(headers[3]));
+        assert_eq!(chain.store().len(), 10);
+
+        // already known headers
+        assert!(chain.connect(2, &headers[2..6]).unwrap().is_empty());
+        // overlapping headers
+        let more = branch(Some(&headers[9]), 3, 100);
+        let mut overlap = headers[8..].to_vec();
+        overlap.extend(more.clone());
+        let events = chain.connect(8, &overlap).unwrap();
+        assert_eq!(events, vec![ChainEvent::Connected(10..=12)]);
+        assert_eq!(chain.tip(), Some(more[2]));
+    }
+
+    #[test]
+    fn connect_errors() {
+        let headers = branch(None, 10, 0);
+        let mut chain = chain(&headers[..5]);
+
+        assert!(matches!(
+            chain.connect(6, &headers[6..]),
+            Err(Error::Gap {
+                expected: 5,
+                got: 6
+            })
+        ));
+
+        // does not link to the stored tip
+        let other = branch(Some(&headers[2]), 3, 100);
+        assert!(matches!(chain.connect(5, &other[2..]), Err(Error::Fork(5))));
+
+        // not chained
+        assert!(matches!(
+            chain.connect(5, &[headers[5], headers[7]]),
+            Err(Error::Electrum(electrum::Error::HeaderNotChained(1)))
+        ));
+
+        let other_genesis = branch(None, 2, 100);
+        assert!(matches!(
+            chain.connect(0, &other_genesis),
+            Err(Error::GenesisMismatch)
+        ));
+        assert_eq!(chain.height(), Some(4));
+    }
+
+    #[test]
+    fn reorg() {
+        let headers = branch(None, 10, 0);
+        let mut chain = chain(&headers);
+
+        // a longer branch forking after height 5
+        let fork = branch(Some(&headers[5]), 6, 100);
+        let events = chain.connect(6, &fork).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                ChainEvent::Reorg {
+                    fork_height: 5,
+                    disconnected: 6..=9
+                },
+                ChainEvent::Connected(6..=11)
+            ]
+        );
+        assert_eq!(chain.height(), Some(11));
+        assert_eq!(chain.header(5), Some(headers[5]));
+        assert_eq!(chain.header(6), Some(fork[0]));
+
+        // a shorter branch replacing the tip
+        let fork = branch(Some(&chain.header(9).unwrap()), 1, 200);
+        let events = chain.connect(10, &fork).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                ChainEvent::Reorg {
+                    fork_height: 9,
+                    disconnected: 10..=11
+                },
+                ChainEvent::Connected(10..=10)
+            ]
+        );
+        assert_eq!(chain.tip(), Some(fork[0]));
+    }
+
+    #[test]
+    fn store_rows() {
+        let headers = branch(None, 3, 0);
+        let chain = chain(&headers);
+        let store = chain.into_store();
+        let row = store.get(1).unwrap();
+        assert_eq!(HeaderRow::from_db_row(row).header, headers[1]);
+        assert!(store.get(3).is_none());
+
+        // a chain can be restored from a store
+        let chain = HeaderChain::new(store);
+        assert_eq!(chain.tip(), Some(headers[2]));
+    }
+
+    #[test]
+    fn notification() {
+        let headers = branch(None, 3, 0);
+        let mut chain = chain(&headers[..2]);
+        // not connected, must not be used for a notification that connects
+        let mut client = Client::new(RawClient::new_tcp("127.0.0.1", 1));
+
+        let hex = miniscript::bitcoin::consensus::encode::serialize_hex(&headers[2]);
+        let notif = Response::HeaderNotif(HeaderNotification::Single(SingleHeaderNotif {
+            id: Id::Number(0),
+            header: Header {
+                height: 2,
+                raw_header: hex,
+            },
+        }));
+        let events = chain.handle_notification(&mut client, &notif).unwrap();
+        assert_eq!(events, vec![ChainEvent::Connected(2..=2)]);
+        // same tip notified twice
+        assert!(chain
+            .handle_notification(&mut client, &notif)
+            .unwrap()
+            .is_empty());
+        // other responses are ignored
+        assert!(chain
+            .handle_notification(
+                &mut client,
+                &Response::Ping(electrum::response::PingResponse {
+                    id: Id::Number(0),
+                    result: None
+                })
+            )
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1 +1,565 @@
+use std::collections::{HashMap, VecDeque};
 
+use crate::{
+    electrum::{
+        self,
+        call::{
+            ElectrumBatch, ElectrumRequest, GetFeatures, GetHeader, GetHistory, GetMempool,
+            GetVerboseTransaction,
+        },
+        capabilities::Capabilities,
+        method::{CustomMethod, Method},
+        request::Request,
+        response::{
+            parse_str_response_with, BatchResponses, ErrorResponse, HistoryResult, Response,
+            RpcErrorCode, Strictness, VerboseTx,
+        },
+        script::ToScriptHash,
+        types::{Id, StatusHash},
+        verify::{verify_block_txids, HeaderSource},
+        version::ProtocolVersion,
+    },
+    raw_client::{self, Client as RawClient},
+};
+use miniscript::bitcoin::{block::Header as BlockHeader, Address, Network, Txid};
+
+/// Number of `blockchain.transaction.id_from_pos` requests sent per batch.
+const TX_FROM_POS_BATCH: usize = 100;
+/// Number of times a history is fetched again if it does not match its status.
+const HISTORY_RETRIES: usize = 2;
+
+#[derive(Debug)]
+pub enum Error {
+    RawClient(raw_client::Error),
+    Electrum(electrum::Error),
+    Rpc(ErrorResponse),
+    WrongResponse,
+    /// The header at this height is missing from the local header source.
+    MissingHeader(usize),
+    /// The network of the client is needed, see [`Client::network()`].
+    NetworkNotSet,
+}
+
+impl From<raw_client::Error> for Error {
+    fn from(value: raw_client::Error) -> Self {
+        match value {
+            raw_client::Error::Electrum(e) => Error::Electrum(e),
+            e => Error::RawClient(e),
+        }
+    }
+}
+
+impl From<electrum::Error> for Error {
+    fn from(value: electrum::Error) -> Self {
+        Error::Electrum(value)
+    }
+}
+
+fn typed_error(e: electrum::Error) -> Error {
+    match e {
+        electrum::Error::WrongMethod => Error::WrongResponse,
+        e => Error::Electrum(e),
+    }
+}
+
+/// A blocking client that keeps track of in-flight requests and of the
+/// capabilities of the server it is connected to.
+#[derive(Debug)]
+pub struct Client {
+    raw: RawClient,
+    index: HashMap<Id, Request>,
+    next_id: usize,
+    // responses & notifications received while waiting for another response
+    pending: VecDeque<Response>,
+    capabilities: Option<Capabilities>,
+    strictness: Strictness,
+    network: Option<Network>,
+}
+
+impl Client {
+    pub fn new(raw: RawClient) -> Self {
+        Self {
+            raw,
+            index: HashMap::new(),
+            next_id: 0,
+            pending: VecDeque::new(),
+            capabilities: None,
+            strictness: Strictness::Strict,
+            network: None,
+        }
+    }
+
+    /// Set how the messages matching none of the modelled responses &
+    /// notifications are handled, long-running clients likely want
+    /// [`Strictness::Skip`] or [`Strictness::Lenient`].
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    /// Set the network the server must be on, it's checked on
+    /// [`Client::negotiate()`] & returned addresses are validated against it.
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    pub fn set_network(&mut self, network: Option<Network>) {
+        self.network = network;
+    }
+
+    pub fn connect(&mut self) -> Result<(), Error> {
+        Ok(self.raw.try_connect()?)
+    }
+
+    /// Check the server is on `network`, fails w/
+    /// [`electrum::Error::WrongNetwork`] otherwise. Servers expect
+    /// `server.version` as first message, see [`Client::negotiate()`].
+    pub fn check_network(&mut self, network: Network) -> Result<(), Error> {
+        Ok(self.call(GetFeatures)?.check_network(network)?)
+    }
+
+    pub fn raw(&mut self) -> &mut RawClient {
+        &mut self.raw
+    }
+
+    /// Negotiate the protocol version with the server, methods not supported
+    /// by the negotiated version and the detected server software will then
+    /// fail locally. If the server software or its version can't be detected
+    /// from the `server.version` response, `server.features` is also queried.
+    /// If a network is set, the genesis hash returned by `server.features`, or
+    /// the hash of the header at height 0 w/o `server.features`, is checked
+    /// against it.
+    pub fn negotiate(
+        &mut self,
+        client_name: &str,
+        min: ProtocolVersion,
+        max: ProtocolVersion,
+    ) -> Result<&Capabilities, Error> {
+        // NOTE: the capabilities of a previous negotiation must not gate the
+        // requests of this one
+        self.capabilities = None;
+        let request = Request::version_range(client_name.into(), min.to_string(), max.to_string());
+        let mut capabilities = match self.request(request)? {
+            Response::Version(r) => Capabilities::from_version_response(&r)?,
+            Response::Error(e) => return Err(Error::Rpc(e)),
+            _ => return Err(Error::WrongResponse),
+        };
+        let features = if capabilities.supports(&Method::Features)
+            && (self.network.is_some() || capabilities.is_partial())
+        {
+            match self.call(GetFeatures) {
+                Ok(features) => Some(features),
+                // NOTE: `server.features` is optional
+                Err(Error::Rpc(_)) => None,
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+        if let Some(network) = self.network {
+            match &features {
+                Some(features) => features.check_network(network)?,
+                None => {
+                    let got = self.call(GetHeader(0))?.block_hash();
+                    if got != electrum::network::genesis_hash(network) {
+                        return Err(electrum::Error::WrongNetwork {
+                            expected: network,
+                            got,
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        if let Some(features) = &features {
+            capabilities.update_from_features(features);
+        }
+        Ok(self.capabilities.insert(capabilities))
+    }
+
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Option<Capabilities>) {
+        self.capabilities = capabilities;
+    }
+
+    /// List the methods supported by the server, `None` if the protocol
+    /// version have not been negotiated yet.
+    pub fn supported_methods(&self) -> Option<Vec<Method>> {
+        self.capabilities.as_ref().map(|c| c.supported_methods())
+    }
+
+    fn check(&self, request: &Request) -> Result<(), Error> {
+        if let Some(capabilities) = &self.capabilities {
+            capabilities.check(request)?;
+        }
+        Ok(())
+    }
+
+    fn register(&mut self, mut request: Request) -> Request {
+        request.id = Id::Number(self.next_id);
+        self.next_id += 1;
+        self.index.insert(request.id.clone(), request.clone());
+        request
+    }
+
+    /// Send a request, returns the id attributed to the request.
+    pub fn send(&mut self, request: Request) -> Result<Id, Error> {
+        self.check(&request)?;
+        let request = self.register(request);
+        if let Err(e) = self.raw.try_send(&request) {
+            self.index.remove(&request.id);
+            return Err(e.into());
+        }
+        Ok(request.id)
+    }
+
+    /// Send a batch of requests, returns the ids attributed to the requests.
+    pub fn send_batch(&mut self, requests: Vec<Request>) -> Result<Vec<Id>, Error> {
+        for request in &requests {
+            self.check(request)?;
+        }
+        let requests: Vec<_> = requests.into_iter().map(|r| self.register(r)).collect();
+        let ids: Vec<_> = requests.iter().map(|r| r.id.clone()).collect();
+        if let Err(e) = self.raw.try_send_batch(requests.iter().collect()) {
+            for id in &ids {
+                self.index.remove(id);
+            }
+            return Err(e.into());
+        }
+        Ok(ids)
+    }
+
+    // NOTE: the batch elements failing to parse are logged & dropped
+    fn track(&mut self, results: Vec<Result<Response, electrum::Error>>) -> Vec<Response> {
+        results
+            .into_iter()
+            .filter_map(|r| match r {
+                Ok(r) => {
+                    if let Some(id) = r.id() {
+                        self.index.remove(&id);
+                    }
+                    Some(r)
+                }
+                Err(e) => {
+                    log::warn!("Fail to parse batch element: {:?}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Blocking receive of the next message(s) from the server, returns
+    /// first the responses received while waiting for another response.
+    pub fn recv(&mut self) -> Result<Vec<Response>, Error> {
+        if !self.pending.is_empty() {
+            return Ok(self.pending.drain(..).collect());
+        }
+        let raw = self.raw.recv_str()?;
+        let results = parse_str_response_with(&raw, &self.index, self.strictness)?;
+        Ok(self.track(results))
+    }
+
+    /// Non-blocking version of [`Client::recv()`].
+    pub fn try_recv(&mut self) -> Result<Option<Vec<Response>>, Error> {
+        if !self.pending.is_empty() {
+            return Ok(Some(self.pending.drain(..).collect()));
+        }
+        match self.raw.try_recv_str()? {
+            Some(raw) => {
+                let results = parse_str_response_with(&raw, &self.index, self.strictness)?;
+                Ok(Some(self.track(results)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Dispatch a response received while waiting for `ids`
+    fn dispatch(
+        &mut self,
+        ids: &[Id],
+        id: Id,
+        result: Result<Response, electrum::Error>,
+        received: &mut HashMap<Id, Result<Response, electrum::Error>>,
+    ) {
+        self.index.remove(&id);
+        if ids.contains(&id) {
+            received.insert(id, result);
+        } else {
+            match result {
+                Ok(r) => self.pending.push_back(r),
+                Err(e) => log::warn!("Fail to parse response {}: {:?}", id, e),
+            }
+        }
+    }
+
+    fn wait_for(
+        &mut self,
+        ids: &[Id],
+    ) -> Result<HashMap<Id, Result<Response, electrum::Error>>, Error> {
+        let mut received = HashMap::new();
+        while received.len() < ids.len() {
+            let raw = self.raw.recv_str()?;
+            if !raw.trim_start().starts_with('[') {
+                if let Some(r) = Response::try_parse_with(&raw, &self.index, self.strictness)? {
+                    match r.id() {
+                        Some(id) => self.dispatch(ids, id, Ok(r), &mut received),
+                        None => self.pending.push_back(r),
+                    }
+                }
+                continue;
+            }
+
+            let batch = BatchResponses::parse(&raw, &self.index)?;
+            for id in &batch.duplicates {
+                log::warn!("Duplicate response for request {}", id);
+            }
+            for r in batch.unknown {
+                match r {
+                    Ok(r) => self.pending.push_back(r),
+                    Err(e) => log::warn!("Fail to parse batch element: {:?}", e),
+                }
+            }
+            // NOTE: a batch is answered w/ a single array, the requests of
+            // this batch w/o element in it will never be answered
+            let answered = batch.results.keys().any(|id| ids.contains(id));
+            for (id, result) in batch.results {
+                self.dispatch(ids, id, result, &mut received);
+            }
+            if answered {
+                for id in ids {
+                    if !received.contains_key(id) {
+                        self.index.remove(id);
+                        let missing = electrum::Error::MissingResponse(id.clone());
+                        received.insert(id.clone(), Err(missing));
+                    }
+                }
+            }
+        }
+        Ok(received)
+    }
+
+    /// Send a request and wait for its response, messages received in the
+    /// meantime are kept and can be fetched w/ [`Client::recv()`].
+    pub fn request(&mut self, request: Request) -> Result<Response, Error> {
+        let id = self.send(request)?;
+        let mut received = self.wait_for(std::slice::from_ref(&id))?;
+        Ok(received.remove(&id).ok_or(Error::WrongResponse)??)
+    }
+
+    /// Send a batch of requests and wait for all the responses, the responses
+    /// are returned in the same order than the requests. Fails if any of the
+    /// responses is missing or can't be parsed, see [`Client::batch_results()`].
+    pub fn batch(&mut self, requests: Vec<Request>) -> Result<Vec<Response>, Error> {
+        self.batch_results(requests)?
+            .into_iter()
+            .map(|r| r.map_err(Error::from))
+            .collect()
+    }
+
+    /// Send a batch of requests and wait for all the responses, returns a
+    /// result per request in the same order than the requests: a missing or
+    /// malformed response does not fail the others.
+    pub fn batch_results(
+        &mut self,
+        requests: Vec<Request>,
+    ) -> Result<Vec<Result<Response, electrum::Error>>, Error> {
+        if requests.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = self.send_batch(requests)?;
+        let mut received = self.wait_for(&ids)?;
+        ids.iter()
+            .map(|id| received.remove(id).ok_or(Error::WrongResponse))
+            .collect()
+    }
+
+    /// Send a typed request and wait for its output, see [`ElectrumRequest`].
+    pub fn call<R: ElectrumRequest>(&mut self, request: R) -> Result<R::Output, Error> {
+        match self.request(request.request())? {
+            Response::Error(e) => Err(Error::Rpc(e)),
+            response => request.parse(response).map_err(typed_error),
+        }
+    }
+
+    /// Send a batch of typed requests and wait for their outputs, e.g.
+    /// `(GetBalance(sh), ListUnspent(sh))` returns a
+    /// `(BalanceResult, Vec<UtxoResult>)`. Fails on the first error response.
+    pub fn call_batch<B: ElectrumBatch>(&mut self, batch: B) -> Result<B::Output, Error> {
+        let responses = self.batch(batch.requests())?;
+        let mut outputs = Vec::with_capacity(responses.len());
+        for response in responses {
+            match response {
+                Response::Error(e) => return Err(Error::Rpc(e)),
+                r => outputs.push(r),
+            }
+        }
+        batch.parse(outputs).map_err(typed_error)
+    }
+
+    /// Call the custom method `M` & parse its result.
+    pub fn custom<M: CustomMethod>(&mut self, params: &M::Params) -> Result<M::Result, Error> {
+        match self.request(Request::custom::<M>(params)?)? {
+            Response::Custom(r) => Ok(r.result::<M>()?),
+            Response::Error(e) => Err(Error::Rpc(e)),
+            _ => Err(Error::WrongResponse),
+        }
+    }
+
+    /// Fetch the header at `height` from the server.
+    pub fn header(&mut self, height: usize) -> Result<BlockHeader, Error> {
+        self.call(GetHeader(height))
+    }
+
+    /// Fetch the verbose transaction `txid`, if a network is set the
+    /// addresses of its outputs are checked against it.
+    pub fn verbose_transaction(&mut self, txid: Txid) -> Result<VerboseTx, Error> {
+        let tx = self.call(GetVerboseTransaction(txid))?;
+        if let Some(network) = self.network {
+            tx.check_addresses(network)?;
+        }
+        Ok(tx)
+    }
+
+    /// Fetch the merkle proof of `txid` and check it against the header at
+    /// `height`, the header is taken from `headers` if any or fetched from the
+    /// server otherwise. A transaction should only be considered confirmed
+    /// if this succeeds.
+    pub fn verify_tx(
+        &mut self,
+        txid: Txid,
+        height: usize,
+        headers: Option<&dyn HeaderSource>,
+    ) -> Result<BlockHeader, Error> {
+        let proof = match self.request(Request::tx_get_merkle(txid, height))? {
+            Response::TxGetMerkle(r) => r.result,
+            Response::Error(e) => return Err(Error::Rpc(e)),
+            _ => return Err(Error::WrongResponse),
+        };
+        if proof.block_height() != height {
+            return Err(electrum::Error::InvalidProof.into());
+        }
+        let header = self.trusted_header(height, headers)?;
+        proof.verify(&txid, &header)?;
+        Ok(header)
+    }
+
+    fn trusted_header(
+        &mut self,
+        height: usize,
+        headers: Option<&dyn HeaderSource>,
+    ) -> Result<BlockHeader, Error> {
+        match headers {
+            Some(headers) => headers
+                .header_at(height)
+                .ok_or(Error::MissingHeader(height)),
+            None => self.header(height),
+        }
+    }
+
+    /// List the txids of the block at `height` w/ `blockchain.transaction.id_from_pos`
+    /// until the server returns an error, the list is checked against the
+    /// merkle root of the header taken from `headers` if any or fetched from
+    /// the server otherwise.
+    pub fn block_txids(
+        &mut self,
+        height: usize,
+        headers: Option<&dyn HeaderSource>,
+    ) -> Result<Vec<Txid>, Error> {
+        let header = self.trusted_header(height, headers)?;
+        let mut txids = Vec::new();
+        loop {
+            let start = txids.len();
+            let requests = (start..start + TX_FROM_POS_BATCH)
+                .map(|pos| Request::tx_from_pos(height, pos, false))
+                .collect();
+            for response in self.batch(requests)? {
+                match response {
+                    Response::TxFromposition(r) => txids.push(r.tx.txid()),
+                    // past the last tx of the block
+                    Response::Error(_) => {
+                        verify_block_txids(&txids, &header)?;
+                        return Ok(txids);
+                    }
+                    _ => return Err(Error::WrongResponse),
+                }
+            }
+        }
+    }
+
+    /// Fetch the history of `script` and check it against the `status`
+    /// announced by the server, the history is fetched again on mismatch as
+    /// it may have changed in the meantime. Fails w/
+    /// [`electrum::Error::StatusMismatch`] if the server keeps sending a
+    /// history that does not match its status.
+    pub fn history<S: ToScriptHash>(
+        &mut self,
+        script: S,
+        status: Option<StatusHash>,
+    ) -> Result<Vec<HistoryResult>, Error> {
+        let mut tries = 0;
+        loop {
+            let history = match self.request(Request::sh_get_history(&script))? {
+                Response::SHGetHistory(r) => r.history,
+                Response::Error(e) => return Err(Error::Rpc(e)),
+                _ => return Err(Error::WrongResponse),
+            };
+            let got = StatusHash::from_history(&history);
+            if got == status {
+                return Ok(history);
+            }
+            if tries == HISTORY_RETRIES {
+                return Err(electrum::Error::StatusMismatch {
+                    expected: status,
+                    got,
+                }
+                .into());
+            }
+            tries += 1;
+        }
+    }
+
+    /// Fetch the donation address of the server, checked against the network
+    /// of the client, fails w/ [`Error::NetworkNotSet`] if not set.
+    pub fn donation_address(&mut self) -> Result<Option<Address>, Error> {
+        let network = self.network.ok_or(Error::NetworkNotSet)?;
+        match self.request(Request::donation())? {
+            Response::Donation(r) => Ok(r.address(network).transpose()?),
+            Response::Error(e) => Err(Error::Rpc(e)),
+            _ => Err(Error::WrongResponse),
+        }
+    }
+
+    /// Fetch the unconfirmed transactions of `script`, falls back to filtering
+    /// its history if the server does not support `blockchain.scripthash.get_mempool`.
+    pub fn mempool<S: ToScriptHash>(&mut self, script: S) -> Result<Vec<HistoryResult>, Error> {
+        let sh = script.to_script_hash();
+        let supported = self
+            .capabilities
+            .as_ref()
+            .map(|c| c.supports(&Method::ScriptHashGetMempool))
+            .unwrap_or(true);
+        if supported {
+            match self.call(GetMempool(sh)) {
+                Err(Error::Rpc(e)) if e.error.code == RpcErrorCode::MethodNotFound => {}
+                result => return result,
+            }
+        }
+        let history = self.call(GetHistory(sh))?;
+        Ok(history
+            .into_iter()
+            .filter(|tx| !tx.is_confirmed())
+            .collect())
+    }
+
+    pub fn close(&mut self) -> Result<(), Error> {
+        Ok(self.raw.close()?)
+    }
+}
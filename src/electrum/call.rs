@@ -0,0 +1,490 @@
+use miniscript::bitcoin::{
+    block::Header as BlockHeader, consensus::encode::serialize_hex, FeeRate, OutPoint, Transaction,
+    Txid, Weight,
+};
+
+use super::{
+    params::VersionKind,
+    request::Request,
+    response::*,
+    types::{ScriptHash, StatusHash},
+    Error,
+};
+
+/// A request whose response type is known at compile time, see
+/// `Client::call()`.
+pub trait ElectrumRequest {
+    type Output;
+
+    fn request(&self) -> Request;
+
+    /// Extract the output from the response to [`ElectrumRequest::request()`],
+    /// fails w/ [`Error::WrongMethod`] if it answers another request.
+    fn parse(&self, response: Response) -> Result<Self::Output, Error>;
+}
+
+/// A batch of [`ElectrumRequest`]s, implemented for tuples of up to 8
+/// requests & for `Vec`s of requests of the same kind, see
+/// `Client::call_batch()`.
+pub trait ElectrumBatch {
+    type Output;
+
+    fn requests(&self) -> Vec<Request>;
+
+    /// Parse the responses, in the same order than the requests.
+    fn parse(&self, responses: Vec<Response>) -> Result<Self::Output, Error>;
+}
+
+macro_rules! electrum_request {
+    ($name:ty => $output:ty, |$s:ident| $request:expr, $pattern:pat => $parse:expr) => {
+        impl ElectrumRequest for $name {
+            type Output = $output;
+
+            fn request(&self) -> Request {
+                #[allow(unused_variables)]
+                let $s = self;
+                $request
+            }
+
+            fn parse(&self, response: Response) -> Result<Self::Output, Error> {
+                #[allow(unused_variables)]
+                let $s = self;
+                match response {
+                    $pattern => $parse,
+                    _ => Err(Error::WrongMethod),
+                }
+            }
+        }
+    };
+}
+
+/// `server.ping`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ping;
+electrum_request!(Ping => (), |s| Request::ping(), Response::Ping(_) => Ok(()));
+
+/// `server.version`, returns the server software name & the negotiated
+/// protocol version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerVersion {
+    pub client_name: String,
+    pub protocol: VersionKind,
+}
+electrum_request!(
+    ServerVersion => (String, VersionKind),
+    |s| match &s.protocol {
+        VersionKind::Single(v) => Request::version(s.client_name.clone(), v.clone()),
+        VersionKind::MinMax(min, max) => {
+            Request::version_range(s.client_name.clone(), min.clone(), max.clone())
+        }
+    },
+    Response::Version(r) => Ok(r.version.0)
+);
+
+/// `server.banner`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetBanner;
+electrum_request!(GetBanner => String, |s| Request::banner(), Response::Banner(r) => Ok(r.result));
+
+/// `server.donation_address`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetDonationAddress;
+electrum_request!(
+    GetDonationAddress => Option<String>,
+    |s| Request::donation(),
+    Response::Donation(r) => Ok(r.address)
+);
+
+/// `server.features`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetFeatures;
+electrum_request!(
+    GetFeatures => FeaturesResult,
+    |s| Request::features(),
+    Response::Features(r) => Ok(r.features)
+);
+
+/// `server.peers.subscribe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListPeers;
+electrum_request!(
+    ListPeers => Vec<Peer>,
+    |s| Request::subscribe_peers(),
+    Response::ListPeers(r) => Ok(r.peers)
+);
+
+/// `server.add_peer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddPeer(pub FeaturesResult);
+electrum_request!(
+    AddPeer => bool,
+    |s| Request::add_peer(s.0.clone()),
+    Response::AddPeer(r) => Ok(r.result)
+);
+
+/// `blockchain.block.header`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetHeader(pub usize);
+electrum_request!(
+    GetHeader => BlockHeader,
+    |s| Request::header(s.0),
+    Response::Header(r) => r.header()
+);
+
+/// `blockchain.block.header` w/ a checkpoint, the proof is not verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetHeaderWithProof {
+    pub height: usize,
+    pub cp_height: usize,
+}
+electrum_request!(
+    GetHeaderWithProof => HeaderWithProof,
+    |s| Request::header_with_checkpoint(s.height, s.cp_height),
+    Response::HeaderWithProof(r) => Ok(r.header)
+);
+
+/// `blockchain.block.headers`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetHeaders {
+    pub start: usize,
+    pub count: usize,
+}
+electrum_request!(
+    GetHeaders => Headers,
+    |s| Request::headers(s.start, s.count),
+    Response::Headers(r) => Ok(r.headers)
+);
+
+/// `blockchain.block.headers` w/ a checkpoint, the proof is not verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetHeadersWithProof {
+    pub start: usize,
+    pub count: usize,
+    pub cp_height: usize,
+}
+electrum_request!(
+    GetHeadersWithProof => HeadersWithProof,
+    |s| Request::headers_with_checkpoint(s.start, s.count, s.cp_height),
+    Response::HeadersWithProof(r) => Ok(r.headers)
+);
+
+/// `blockchain.headers.subscribe`, returns the current tip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscribeHeaders;
+electrum_request!(
+    SubscribeHeaders => Header,
+    |s| Request::subscribe_headers(),
+    Response::HeaderNotif(HeaderNotification::Single(r)) => Ok(r.header)
+);
+
+/// `blockchain.estimatefee`, `None` if the server can't estimate the fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimateFee(pub u16);
+electrum_request!(
+    EstimateFee => Option<FeeRate>,
+    |s| Request::estimate_fee(s.0),
+    Response::EstimateFee(r) => Ok(r.fee_rate())
+);
+
+/// `blockchain.relayfee`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetRelayFee;
+electrum_request!(
+    GetRelayFee => Option<FeeRate>,
+    |s| Request::relay_fee(),
+    Response::RelayFee(r) => Ok(r.fee_rate())
+);
+
+/// `mempool.get_fee_histogram`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetFeeHistogram;
+electrum_request!(
+    GetFeeHistogram => Vec<(FeeRate, Weight)>,
+    |s| Request::get_fee_histogram(),
+    Response::FeeHistogram(r) => Ok(r.fee_rates())
+);
+
+/// `mempool.get_info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetMempoolInfo;
+electrum_request!(
+    GetMempoolInfo => MempoolInfo,
+    |s| Request::mempool_info(),
+    Response::MempoolInfo(r) => Ok(r.info)
+);
+
+/// `blockchain.scripthash.get_balance`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetBalance(pub ScriptHash);
+electrum_request!(
+    GetBalance => BalanceResult,
+    |s| Request::sh_get_balance(s.0),
+    Response::SHGetBalance(r) => Ok(r.balance)
+);
+
+/// `blockchain.scripthash.get_history`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetHistory(pub ScriptHash);
+electrum_request!(
+    GetHistory => Vec<HistoryResult>,
+    |s| Request::sh_get_history(s.0),
+    Response::SHGetHistory(r) => Ok(r.history)
+);
+
+/// `blockchain.scripthash.get_mempool`, see `Client::mempool()` for a
+/// fallback on servers w/o it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetMempool(pub ScriptHash);
+electrum_request!(
+    GetMempool => Vec<HistoryResult>,
+    |s| Request::sh_get_mempool(s.0),
+    Response::SHGetMempool(r) => Ok(r.mempool)
+);
+
+/// `blockchain.scripthash.listunspent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListUnspent(pub ScriptHash);
+electrum_request!(
+    ListUnspent => Vec<UtxoResult>,
+    |s| Request::sh_list_unspent(s.0),
+    Response::SHListUnspent(r) => Ok(r.unspent)
+);
+
+/// `blockchain.scripthash.subscribe`, returns the current status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscribeScriptHash(pub ScriptHash);
+electrum_request!(
+    SubscribeScriptHash => Option<StatusHash>,
+    |s| Request::subscribe_sh(s.0),
+    Response::SHSubscribe(r) => r.status_hash()
+);
+
+/// `blockchain.scripthash.unsubscribe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsubscribeScriptHash(pub ScriptHash);
+electrum_request!(
+    UnsubscribeScriptHash => bool,
+    |s| Request::unsubscribe_sh(s.0),
+    Response::SHUnsubscribe(r) => Ok(r.result)
+);
+
+/// `blockchain.transaction.get`, the txid of the returned transaction is
+/// checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetTransaction(pub Txid);
+electrum_request!(
+    GetTransaction => Transaction,
+    |s| Request::tx_get(s.0),
+    Response::TxGet(r) => r.result.checked_transaction(&s.0)
+);
+
+/// `blockchain.transaction.get` w/ `verbose=true`, the txid of the returned
+/// transaction is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetVerboseTransaction(pub Txid);
+electrum_request!(
+    GetVerboseTransaction => VerboseTx,
+    |s| Request::tx_get_verbose(s.0),
+    Response::TxGet(r) => {
+        r.result.checked_transaction(&s.0)?;
+        match r.result {
+            TxGetResult::Verbose(tx) => Ok(*tx),
+            TxGetResult::Raw(_) => Err(Error::WrongMethod),
+        }
+    }
+);
+
+/// `blockchain.transaction.get_merkle`, the proof is not verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetMerkle {
+    pub txid: Txid,
+    pub height: usize,
+}
+electrum_request!(
+    GetMerkle => GetMerkleResult,
+    |s| Request::tx_get_merkle(s.txid, s.height),
+    Response::TxGetMerkle(r) => Ok(r.result)
+);
+
+/// `blockchain.transaction.id_from_pos`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxFromPos {
+    pub height: usize,
+    pub tx_pos: usize,
+    pub merkle: bool,
+}
+electrum_request!(
+    TxFromPos => TxfromPosResult,
+    |s| Request::tx_from_pos(s.height, s.tx_pos, s.merkle),
+    Response::TxFromposition(r) => Ok(r.tx)
+);
+
+/// `blockchain.transaction.broadcast`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broadcast(pub Transaction);
+electrum_request!(
+    Broadcast => Txid,
+    |s| Request::tx_broadcast(serialize_hex(&s.0)),
+    Response::TxBroadcast(r) => Ok(r.txid)
+);
+
+/// `blockchain.transaction.broadcast_package`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastPackage(pub Vec<Transaction>);
+electrum_request!(
+    BroadcastPackage => BroadcastPackageResult,
+    |s| Request::tx_broadcast_package(s.0.iter().map(serialize_hex).collect()),
+    Response::TxBroadcastPackage(r) => Ok(r.result)
+);
+
+/// `blockchain.outpoint.subscribe`, returns the current status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscribeOutpoint(pub OutPoint);
+electrum_request!(
+    SubscribeOutpoint => OutpointStatus,
+    |s| Request::subscribe_outpoint(s.0),
+    Response::OutpointSubscribe(r) => Ok(r.status)
+);
+
+/// `blockchain.outpoint.unsubscribe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsubscribeOutpoint(pub OutPoint);
+electrum_request!(
+    UnsubscribeOutpoint => bool,
+    |s| Request::unsubscribe_outpoint(s.0),
+    Response::OutpointUnsubscribe(r) => Ok(r.result)
+);
+
+impl<R: ElectrumRequest> ElectrumBatch for Vec<R> {
+    type Output = Vec<R::Output>;
+
+    fn requests(&self) -> Vec<Request> {
+        self.iter().map(R::request).collect()
+    }
+
+    fn parse(&self, responses: Vec<Response>) -> Result<Self::Output, Error> {
+        if responses.len() != self.len() {
+            return Err(Error::BatchParsing);
+        }
+        self.iter()
+            .zip(responses)
+            .map(|(request, response)| request.parse(response))
+            .collect()
+    }
+}
+
+macro_rules! electrum_batch {
+    ($($r:ident $i:tt),+) => {
+        impl<$($r: ElectrumRequest),+> ElectrumBatch for ($($r,)+) {
+            type Output = ($($r::Output,)+);
+
+            fn requests(&self) -> Vec<Request> {
+                vec![$(self.$i.request()),+]
+            }
+
+            fn parse(&self, responses: Vec<Response>) -> Result<Self::Output, Error> {
+                let mut responses = responses.into_iter();
+                let output = ($(self.$i.parse(responses.next().ok_or(Error::BatchParsing)?)?,)+);
+                if responses.next().is_some() {
+                    return Err(Error::BatchParsing);
+                }
+                Ok(output)
+            }
+        }
+    };
+}
+
+electrum_batch!(A 0);
+electrum_batch!(A 0, B 1);
+electrum_batch!(A 0, B 1, C 2);
+electrum_batch!(A 0, B 1, C 2, D 3);
+electrum_batch!(A 0, B 1, C 2, D 3, E 4);
+electrum_batch!(A 0, B 1, C 2, D 3, E 4, F 5);
+electrum_batch!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+electrum_batch!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use miniscript::bitcoin::{hashes::Hash, Amount, SignedAmount};
+
+    use super::*;
+    use crate::electrum::types::Id;
+
+    fn parse(requests: Vec<Request>, raw: &str) -> Vec<Response> {
+        let mut index = HashMap::new();
+        for (i, request) in requests.into_iter().enumerate() {
+            index.insert(Id::Number(i), request.id(i));
+        }
+        parse_str_response(raw, &index)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn request() {
+        let sh = ScriptHash::all_zeros();
+        assert_eq!(GetBalance(sh).request(), Request::sh_get_balance(sh));
+        assert_eq!(
+            ServerVersion {
+                client_name: "test".into(),
+                protocol: VersionKind::MinMax("1.4".into(), "1.6".into()),
+            }
+            .request(),
+            Request::version_range("test".into(), "1.4".into(), "1.6".into())
+        );
+
+        let responses = parse(
+            vec![GetBanner.request()],
+            r#"{"jsonrpc":"2.0","id":0,"result":"hello"}"#,
+        );
+        let response = responses.into_iter().next().unwrap();
+        assert_eq!(GetBanner.parse(response).unwrap(), "hello");
+
+        let responses = parse(
+            vec![GetBanner.request()],
+            r#"{"jsonrpc":"2.0","id":0,"result":"hello"}"#,
+        );
+        let response = responses.into_iter().next().unwrap();
+        assert!(matches!(Ping.parse(response), Err(Error::WrongMethod)));
+    }
+
+    #[test]
+    fn batch() {
+        let sh = ScriptHash::all_zeros();
+        let batch = (GetBalance(sh), ListUnspent(sh), Ping);
+        let raw = r#"[
+            {"jsonrpc":"2.0","id":0,"result":{"confirmed":1000,"unconfirmed":-200}},
+            {"jsonrpc":"2.0","id":1,"result":[{"height":0,"tx_hash":"0000000000000000000000000000000000000000000000000000000000000000","tx_pos":1,"value":800}]},
+            {"jsonrpc":"2.0","id":2,"result":null}
+        ]"#;
+        let responses = parse(batch.requests(), raw);
+        let (balance, unspent, ()) = batch.parse(responses).unwrap();
+        assert_eq!(balance.total(), SignedAmount::from_sat(800));
+        assert_eq!(unspent[0].value, Amount::from_sat(800));
+
+        // too few responses
+        let responses = parse(batch.requests(), raw);
+        let responses = responses.into_iter().take(2).collect();
+        assert!(matches!(batch.parse(responses), Err(Error::BatchParsing)));
+        // out of order
+        let mut responses = parse(batch.requests(), raw);
+        responses.swap(0, 1);
+        assert!(matches!(batch.parse(responses), Err(Error::WrongMethod)));
+
+        let batch = vec![EstimateFee(1), EstimateFee(6)];
+        let raw = r#"[
+            {"jsonrpc":"2.0","id":0,"result":-1},
+            {"jsonrpc":"2.0","id":1,"result":0.00001}
+        ]"#;
+        let responses = parse(batch.requests(), raw);
+        let fees = batch.parse(responses).unwrap();
+        assert_eq!(
+            fees,
+            vec![None, Some(FeeRate::from_sat_per_vb_unchecked(1))]
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::{
+    method::Method,
+    params::VersionKind,
+    request::Request,
+    response::{FeaturesResult, VersionResponse},
+    version::ProtocolVersion,
+    Error,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerSoftware {
+    Electrs,
+    /// The esplora fork of electrs, versioned apart from electrs.
+    Esplora,
+    ElectrumX,
+    Fulcrum,
+    Unknown(String),
+}
+
+impl ServerSoftware {
+    /// Detect the server software from the server name returned by
+    /// `server.version` or the `server_version` field of `server.features`.
+    pub fn parse(server_version: &str) -> Self {
+        let lower = server_version.to_lowercase();
+        if lower.starts_with("electrs-esplora") {
+            Self::Esplora
+        } else if lower.starts_with("electrs") {
+            Self::Electrs
+        } else if lower.starts_with("electrumx") {
+            Self::ElectrumX
+        } else if lower.starts_with("fulcrum") {
+            Self::Fulcrum
+        } else {
+            Self::Unknown(server_version.into())
+        }
+    }
+
+    /// Whether `version` of the software has `quirk`, if the version is
+    /// unknown the quirk is assumed not fixed.
+    pub fn has_quirk(&self, version: Option<SoftwareVersion>, quirk: &Quirk) -> bool {
+        QUIRKS.iter().any(|known| {
+            known.software == *self
+                && known.quirk == *quirk
+                && match (known.fixed_in, version) {
+                    (Some(fixed_in), Some(version)) => version < fixed_in,
+                    _ => true,
+                }
+        })
+    }
+}
+
+/// The version of a server software, e.g. `0.10.5` for `electrs/0.10.5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SoftwareVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl SoftwareVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Extract the version from a server name, e.g. `ElectrumX 1.15.0` or
+    /// `Fulcrum 1.9`, missing components are 0.
+    pub fn parse(server_version: &str) -> Option<Self> {
+        let start = server_version.find(|c: char| c.is_ascii_digit())?;
+        let version = server_version[start..]
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()?;
+        let mut parts = version.split('.').map(|p| p.parse::<u32>().ok());
+        let major = parts.next()??;
+        let minor = parts.next().flatten().unwrap_or(0);
+        let patch = parts.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl Display for SoftwareVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A known deviation of a server software from the protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Quirk {
+    /// The method is not implemented.
+    Unsupported(Method),
+    /// `cp_height` is not supported by `blockchain.block.header(s)`.
+    NoCheckpoint,
+}
+
+struct KnownQuirk {
+    software: ServerSoftware,
+    quirk: Quirk,
+    /// The first version w/o the quirk, if fixed.
+    fixed_in: Option<SoftwareVersion>,
+}
+
+const QUIRKS: &[KnownQuirk] = &[
+    KnownQuirk {
+        software: ServerSoftware::Electrs,
+        quirk: Quirk::Unsupported(Method::ScriptHashGetMempool),
+        fixed_in: None,
+    },
+    // NOTE: added by the rewrite of electrs 0.9
+    KnownQuirk {
+        software: ServerSoftware::Electrs,
+        quirk: Quirk::Unsupported(Method::ScriptHashUnsubscribe),
+        fixed_in: Some(SoftwareVersion::new(0, 9, 0)),
+    },
+    KnownQuirk {
+        software: ServerSoftware::Electrs,
+        quirk: Quirk::Unsupported(Method::Features),
+        fixed_in: Some(SoftwareVersion::new(0, 9, 0)),
+    },
+    KnownQuirk {
+        software: ServerSoftware::Electrs,
+        quirk: Quirk::NoCheckpoint,
+        fixed_in: None,
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub protocol: ProtocolVersion,
+    pub software: ServerSoftware,
+    pub software_version: Option<SoftwareVersion>,
+}
+
+impl Capabilities {
+    pub fn new(protocol: ProtocolVersion, software: ServerSoftware) -> Self {
+        Self {
+            protocol,
+            software,
+            software_version: None,
+        }
+    }
+
+    pub fn with_software_version(mut self, version: SoftwareVersion) -> Self {
+        self.software_version = Some(version);
+        self
+    }
+
+    pub fn from_version_response(response: &VersionResponse) -> Result<Self, Error> {
+        let protocol = match response.protocol() {
+            VersionKind::Single(v) => ProtocolVersion::from_str(v)?,
+            // NOTE: the server should always answer with a single version,
+            // we fallback to the lowest one otherwise
+            VersionKind::MinMax(min, _) => ProtocolVersion::from_str(min)?,
+        };
+        let name = response.server_name();
+        Ok(Self {
+            protocol,
+            software: ServerSoftware::parse(name),
+            software_version: SoftwareVersion::parse(name),
+        })
+    }
+
+    /// Whether the software or its version have not been detected from the
+    /// `server.version` response.
+    pub fn is_partial(&self) -> bool {
+        matches!(self.software, ServerSoftware::Unknown(_)) || self.software_version.is_none()
+    }
+
+    /// Complete the detection w/ the `server_version` field of
+    /// `server.features`, the already detected values are kept.
+    pub fn update_from_features(&mut self, features: &FeaturesResult) {
+        if let ServerSoftware::Unknown(_) = self.software {
+            self.software = ServerSoftware::parse(&features.server_version);
+        }
+        if self.software_version.is_none() {
+            self.software_version = SoftwareVersion::parse(&features.server_version);
+        }
+    }
+
+    pub fn has_quirk(&self, quirk: &Quirk) -> bool {
+        self.software.has_quirk(self.software_version, quirk)
+    }
+
+    pub fn supports(&self, method: &Method) -> bool {
+        method.is_available(self.protocol) && !self.has_quirk(&Quirk::Unsupported(method.clone()))
+    }
+
+    pub fn supports_checkpoint(&self) -> bool {
+        !self.has_quirk(&Quirk::NoCheckpoint)
+    }
+
+    pub fn check(&self, request: &Request) -> Result<(), Error> {
+        if !self.supports(&request.method) {
+            Err(Error::UnsupportedMethod(request.method.clone()))
+        } else if request.params().checkpoint().is_some() && !self.supports_checkpoint() {
+            Err(Error::UnsupportedParams(request.method.clone()))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn supported_methods(&self) -> Vec<Method> {
+        Method::ALL
+            .iter()
+            .filter(|m| self.supports(m))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miniscript::bitcoin::Script;
+
+    use super::*;
+
+    #[test]
+    fn server_software() {
+        assert_eq!(
+            ServerSoftware::parse("electrs/0.10.5"),
+            ServerSoftware::Electrs
+        );
+        assert_eq!(
+            ServerSoftware::parse("electrs-esplora 0.4.1"),
+            ServerSoftware::Esplora
+        );
+        assert_eq!(
+            ServerSoftware::parse("ElectrumX 1.15.0"),
+            ServerSoftware::ElectrumX
+        );
+        assert_eq!(
+            ServerSoftware::parse("Fulcrum 1.9.1"),
+            ServerSoftware::Fulcrum
+        );
+        assert_eq!(
+            ServerSoftware::parse("toto"),
+            ServerSoftware::Unknown("toto".into())
+        );
+    }
+
+    #[test]
+    fn from_version_response() {
+        let response = r#"{"id":0,"jsonrpc":"2.0","result":["electrs/0.10.5","1.4"]}"#;
+        let response: VersionResponse = serde_json::from_str(response).unwrap();
+        let capabilities = Capabilities::from_version_response(&response).unwrap();
+        assert_eq!(capabilities.protocol, ProtocolVersion::V1_4);
+        assert_eq!(capabilities.software, ServerSoftware::Electrs);
+        assert_eq!(
+            capabilities.software_version,
+            Some(SoftwareVersion::new(0, 10, 5))
+        );
+        assert!(!capabilities.is_partial());
+
+        let response = r#"{"id":0,"jsonrpc":"2.0","result":["toto","1.4"]}"#;
+        let response: VersionResponse = serde_json::from_str(response).unwrap();
+        let mut capabilities = Capabilities::from_version_response(&response).unwrap();
+        assert!(capabilities.is_partial());
+        let features: FeaturesResult = serde_json::from_str(
+            r#"{"genesis_hash":"abc","hash_function":"sha256","hosts":{},"protocol_max":"1.4","protocol_min":"1.4","pruning":null,"server_version":"Fulcrum 1.9.1"}"#,
+        )
+        .unwrap();
+        capabilities.update_from_features(&features);
+        assert_eq!(capabilities.software, ServerSoftware::Fulcrum);
+        assert_eq!(
+            capabilities.software_version,
+            Some(SoftwareVersion::new(1, 9, 1))
+        );
+    }
+
+    #[test]
+    fn software_version() {
+        assert_eq!(
+            SoftwareVersion::parse("electrs/0.10.5"),
+            Some(SoftwareVersion::new(0, 10, 5))
+        );
+        assert_eq!(
+            SoftwareVersion::parse("ElectrumX 1.15.0"),
+            Some(SoftwareVersion::new(1, 15, 0))
+        );
+        assert_eq!(
+            SoftwareVersion::parse("Fulcrum 1.9"),
+            Some(SoftwareVersion::new(1, 9, 0))
+        );
+        assert_eq!(
+            SoftwareVersion::parse("electrs-esplora 0.4.1-dev"),
+            Some(SoftwareVersion::new(0, 4, 1))
+        );
+        assert_eq!(SoftwareVersion::parse("toto"), None);
+        assert!(SoftwareVersion::new(1, 9, 0) < SoftwareVersion::new(1, 10, 0));
+        assert_eq!(SoftwareVersion::new(0, 10, 5).to_string(), "0.10.5");
+    }
+
+    #[test]
+    fn quirks() {
+        let get_mempool = Quirk::Unsupported(Method::ScriptHashGetMempool);
+        let version = Some(SoftwareVersion::new(0, 10, 5));
+        assert!(ServerSoftware::Electrs.has_quirk(version, &get_mempool));
+        assert!(ServerSoftware::Electrs.has_quirk(None, &Quirk::NoCheckpoint));
+        assert!(!ServerSoftware::Fulcrum.has_quirk(None, &get_mempool));
+        assert!(!ServerSoftware::ElectrumX.has_quirk(version, &Quirk::NoCheckpoint));
+        assert!(!ServerSoftware::Unknown("toto".into()).has_quirk(None, &get_mempool));
+
+        let electrs = Capabilities::new(ProtocolVersion::V1_4, ServerSoftware::Electrs)
+            .with_software_version(SoftwareVersion::new(0, 10, 5));
+        assert!(!electrs.supports(&Method::ScriptHashGetMempool));
+        assert!(!electrs.supports_checkpoint());
+        let electrumx = Capabilities::new(ProtocolVersion::V1_4, ServerSoftware::ElectrumX);
+        assert!(electrumx.supports(&Method::ScriptHashGetMempool));
+
+        // version gated
+        let unsubscribe = Quirk::Unsupported(Method::ScriptHashUnsubscribe);
+        let old = Some(SoftwareVersion::new(0, 8, 12));
+        assert!(ServerSoftware::Electrs.has_quirk(old, &unsubscribe));
+        assert!(!ServerSoftware::Electrs.has_quirk(version, &unsubscribe));
+        assert!(ServerSoftware::Electrs.has_quirk(None, &unsubscribe));
+        let old = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Electrs)
+            .with_software_version(SoftwareVersion::new(0, 8, 12));
+        assert!(!old.supports(&Method::Features));
+        assert!(!old.supports(&Method::ScriptHashUnsubscribe));
+        let new = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Electrs)
+            .with_software_version(SoftwareVersion::new(0, 9, 0));
+        assert!(new.supports(&Method::Features));
+        assert!(new.supports(&Method::ScriptHashUnsubscribe));
+        assert!(!new.supports(&Method::ScriptHashGetMempool));
+
+        // the electrs versions do not apply to the esplora fork
+        let esplora = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Esplora)
+            .with_software_version(SoftwareVersion::new(0, 4, 1));
+        assert!(esplora.supports(&Method::Features));
+        assert!(esplora.supports(&Method::ScriptHashUnsubscribe));
+    }
+
+    #[test]
+    fn check() {
+        let script = Script::from_bytes(&[0x00]);
+
+        let electrs = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Electrs);
+        assert!(electrs.check(&Request::ping()).is_ok());
+        assert!(matches!(
+            electrs.check(&Request::unsubscribe_sh(script)),
+            Err(Error::UnsupportedMethod(Method::ScriptHashUnsubscribe))
+        ));
+
+        let fulcrum = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Fulcrum);
+        assert!(fulcrum.check(&Request::unsubscribe_sh(script)).is_ok());
+
+        let old = Capabilities::new(ProtocolVersion::V1_4, ServerSoftware::Fulcrum);
+        assert!(old.check(&Request::unsubscribe_sh(script)).is_err());
+
+        assert!(fulcrum
+            .check(&Request::header_with_checkpoint(1, 2))
+            .is_ok());
+        assert!(matches!(
+            electrs.check(&Request::header_with_checkpoint(1, 2)),
+            Err(Error::UnsupportedParams(Method::BlockHeader))
+        ));
+        assert!(matches!(
+            electrs.check(&Request::headers_with_checkpoint(1, 2, 3)),
+            Err(Error::UnsupportedParams(Method::BlockHeaders))
+        ));
+    }
+
+    #[test]
+    fn supported_methods() {
+        let fulcrum = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Fulcrum);
+        let fulcrum_methods = fulcrum.supported_methods();
+        assert!(fulcrum_methods.contains(&Method::ScriptHashUnsubscribe));
+        assert!(!fulcrum_methods.contains(&Method::MempoolGetInfo));
+
+        let electrs = Capabilities::new(ProtocolVersion::V1_4_2, ServerSoftware::Electrs);
+        let methods = electrs.supported_methods();
+        assert_eq!(methods.len(), fulcrum_methods.len() - 3);
+        assert!(!methods.contains(&Method::Features));
+        assert!(!methods.contains(&Method::ScriptHashUnsubscribe));
+        assert!(!methods.contains(&Method::ScriptHashGetMempool));
+        assert!(fulcrum_methods.contains(&Method::ScriptHashGetMempool));
+
+        let v1_6 = Capabilities::new(ProtocolVersion::V1_6, ServerSoftware::Fulcrum);
+        let methods = v1_6.supported_methods();
+        assert!(!methods.contains(&Method::RelayFee));
+        assert!(methods.contains(&Method::MempoolGetInfo));
+        assert!(methods.contains(&Method::OutpointSubscribe));
+    }
+}
@@ -1,7 +1,8 @@
-use miniscript::serde::{Deserialize, Serialize};
+use super::version::ProtocolVersion;
+use miniscript::serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fmt::Debug;
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     #[serde(rename = "server.banner")]
     Banner,
@@ -23,6 +24,8 @@ pub enum Method {
     FeeHistogram,
     #[serde(rename = "server.peers.subscribe")]
     ListPeers,
+    #[serde(rename = "server.add_peer")]
+    AddPeer,
     #[serde(rename = "server.ping")]
     Ping,
     #[serde(rename = "blockchain.relayfee")]
@@ -31,9 +34,9 @@ pub enum Method {
     ScriptHashGetBalance,
     #[serde(rename = "blockchain.scripthash.get_history")]
     ScriptHashGetHistory,
-    // NOTE: not supported by electrs
-    // #[serde(rename = "blockchain.scripthash.get_mempool")]
-    // ScriptHashGetMempool,
+    // NOTE: not supported by electrs, see `capabilities::QUIRKS`
+    #[serde(rename = "blockchain.scripthash.get_mempool")]
+    ScriptHashGetMempool,
     #[serde(rename = "blockchain.scripthash.listunspent")]
     ScriptHashListUnspent,
     #[serde(rename = "blockchain.scripthash.subscribe")]
@@ -48,6 +51,33 @@ pub enum Method {
     TransactionFromPosition,
     #[serde(rename = "server.version")]
     Version,
+    #[serde(rename = "blockchain.outpoint.subscribe")]
+    OutpointSubscribe,
+    #[serde(rename = "blockchain.outpoint.unsubscribe")]
+    OutpointUnsubscribe,
+    #[serde(rename = "mempool.get_info")]
+    MempoolGetInfo,
+    #[serde(rename = "blockchain.transaction.broadcast_package")]
+    TransactionBroadcastPackage,
+    /// A method not modelled here, e.g. a server extension, see [`CustomMethod`].
+    #[serde(untagged)]
+    Custom(String),
+}
+
+/// A method not modelled by [`Method`], e.g. an extension of a server
+/// implementation, requests are built w/ [`super::request::Request::custom()`]
+/// & results parsed w/ [`super::response::CustomResponse::result()`].
+pub trait CustomMethod {
+    /// The method name, e.g. `blockchain.scripthash.get_first_use`.
+    const NAME: &'static str;
+    /// The params, serialized as is: usually a tuple (positional params) or
+    /// a struct (named params).
+    type Params: Serialize;
+    type Result: DeserializeOwned;
+
+    fn method() -> Method {
+        Method::Custom(Self::NAME.into())
+    }
 }
 
 impl Debug for Method {
@@ -63,10 +93,12 @@ impl Debug for Method {
             Self::HeadersSubscribe => write!(f, "blockchain.headers.subscribe"),
             Self::FeeHistogram => write!(f, "mempool.get_fee_histogram"),
             Self::ListPeers => write!(f, "server.peers.subscribe"),
+            Self::AddPeer => write!(f, "server.add_peer"),
             Self::Ping => write!(f, "server.ping"),
             Self::RelayFee => write!(f, "blockchain.relayfee"),
             Self::ScriptHashGetBalance => write!(f, "blockchain.scripthash.get_balance"),
             Self::ScriptHashGetHistory => write!(f, "blockchain.scripthash.get_history"),
+            Self::ScriptHashGetMempool => write!(f, "blockchain.scripthash.get_mempool"),
             Self::ScriptHashListUnspent => write!(f, "blockchain.scripthash.listunspent"),
             Self::ScriptHashSubscribe => write!(f, "blockchain.scripthash.subscribe"),
             Self::ScriptHashUnsubscribe => write!(f, "blockchain.scripthash.unsubscribe"),
@@ -74,15 +106,109 @@ impl Debug for Method {
             Self::TransactionGetMerkle => write!(f, "blockchain.transaction.get_merkle"),
             Self::TransactionFromPosition => write!(f, "blockchain.transaction.id_from_pos"),
             Self::Version => write!(f, "server.version"),
-            // NOTE: not supported by electrs
-            // Self::ScriptHashGetMempool => write!(f, "blockchain.scripthash.get_mempool"),
+            Self::OutpointSubscribe => write!(f, "blockchain.outpoint.subscribe"),
+            Self::OutpointUnsubscribe => write!(f, "blockchain.outpoint.unsubscribe"),
+            Self::MempoolGetInfo => write!(f, "mempool.get_info"),
+            Self::TransactionBroadcastPackage => {
+                write!(f, "blockchain.transaction.broadcast_package")
+            }
+            Self::Custom(name) => write!(f, "{}", name),
         }
     }
 }
 
+impl Method {
+    pub const ALL: [Method; 27] = [
+        Self::Banner,
+        Self::BlockHeader,
+        Self::BlockHeaders,
+        Self::TransactionBroadcast,
+        Self::Donation,
+        Self::EstimateFee,
+        Self::Features,
+        Self::HeadersSubscribe,
+        Self::FeeHistogram,
+        Self::ListPeers,
+        Self::AddPeer,
+        Self::Ping,
+        Self::RelayFee,
+        Self::ScriptHashGetBalance,
+        Self::ScriptHashGetHistory,
+        Self::ScriptHashGetMempool,
+        Self::ScriptHashListUnspent,
+        Self::ScriptHashSubscribe,
+        Self::ScriptHashUnsubscribe,
+        Self::TransactionGet,
+        Self::TransactionGetMerkle,
+        Self::TransactionFromPosition,
+        Self::Version,
+        Self::OutpointSubscribe,
+        Self::OutpointUnsubscribe,
+        Self::MempoolGetInfo,
+        Self::TransactionBroadcastPackage,
+    ];
+
+    /// The method named `name`, [`Method::Custom`] if it is not modelled.
+    pub fn from_name(name: &str) -> Self {
+        Self::ALL
+            .iter()
+            .find(|m| format!("{:?}", m) == name)
+            .cloned()
+            .unwrap_or_else(|| Self::Custom(name.into()))
+    }
+
+    /// Protocol version the method was introduced in.
+    /// https://electrumx-spesmilo.readthedocs.io/en/latest/protocol-changes.html
+    pub fn since(&self) -> ProtocolVersion {
+        match self {
+            Self::Banner
+            | Self::Donation
+            | Self::EstimateFee
+            | Self::HeadersSubscribe
+            | Self::ListPeers
+            | Self::RelayFee
+            | Self::TransactionBroadcast
+            | Self::TransactionGet
+            | Self::TransactionGetMerkle
+            | Self::Version
+            // NOTE: availability of custom methods is not tracked
+            | Self::Custom(_) => ProtocolVersion::V1_0,
+            Self::AddPeer
+            | Self::Features
+            | Self::ScriptHashGetBalance
+            | Self::ScriptHashGetHistory
+            | Self::ScriptHashGetMempool
+            | Self::ScriptHashListUnspent
+            | Self::ScriptHashSubscribe => ProtocolVersion::V1_1,
+            Self::BlockHeaders | Self::FeeHistogram | Self::Ping => ProtocolVersion::V1_2,
+            Self::BlockHeader => ProtocolVersion::V1_3,
+            Self::TransactionFromPosition => ProtocolVersion::V1_4,
+            Self::ScriptHashUnsubscribe => ProtocolVersion::V1_4_2,
+            Self::OutpointSubscribe
+            | Self::OutpointUnsubscribe
+            | Self::MempoolGetInfo
+            | Self::TransactionBroadcastPackage => ProtocolVersion::V1_6,
+        }
+    }
+
+    /// Protocol version the method was removed in, if any.
+    pub fn until(&self) -> Option<ProtocolVersion> {
+        match self {
+            // NOTE: replaced by `mempool.get_info`
+            Self::RelayFee => Some(ProtocolVersion::V1_6),
+            _ => None,
+        }
+    }
+
+    pub fn is_available(&self, version: ProtocolVersion) -> bool {
+        self.since() <= version && self.until().map(|v| version < v).unwrap_or(true)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Method::*;
+    use super::*;
 
     macro_rules! debug_json {
         ($value:expr) => {{
@@ -103,6 +229,10 @@ mod tests {
     #[test]
     fn debug() {
         debug_json!(Version);
+        debug_json!(OutpointSubscribe);
+        debug_json!(OutpointUnsubscribe);
+        debug_json!(MempoolGetInfo);
+        debug_json!(TransactionBroadcastPackage);
         debug_json!(TransactionFromPosition);
         debug_json!(TransactionGetMerkle);
         debug_json!(TransactionGet);
@@ -110,10 +240,12 @@ mod tests {
         debug_json!(ScriptHashSubscribe);
         debug_json!(ScriptHashListUnspent);
         debug_json!(ScriptHashGetHistory);
+        debug_json!(ScriptHashGetMempool);
         debug_json!(ScriptHashGetBalance);
         debug_json!(RelayFee);
         debug_json!(Ping);
         debug_json!(ListPeers);
+        debug_json!(AddPeer);
         debug_json!(FeeHistogram);
         debug_json!(HeadersSubscribe);
         debug_json!(Features);
@@ -123,5 +255,40 @@ mod tests {
         debug_json!(BlockHeaders);
         debug_json!(BlockHeader);
         debug_json!(Banner);
+        debug_json!(Custom("blockchain.scripthash.get_first_use".into()));
+    }
+
+    #[test]
+    fn custom() {
+        let method: Method = serde_json::from_str(r#""server.toto""#).unwrap();
+        assert_eq!(method, Custom("server.toto".into()));
+        let method: Method = serde_json::from_str(r#""server.banner""#).unwrap();
+        assert_eq!(method, Banner);
+        assert_eq!(
+            serde_json::to_string(&Custom("server.toto".into())).unwrap(),
+            r#""server.toto""#
+        );
+        assert!(!Method::ALL.contains(&Custom("server.toto".into())));
+        assert_eq!(
+            Method::from_name("server.toto"),
+            Custom("server.toto".into())
+        );
+        for method in Method::ALL {
+            assert_eq!(Method::from_name(&format!("{:?}", method)), method);
+        }
+    }
+
+    #[test]
+    fn availability() {
+        assert!(Ping.is_available(ProtocolVersion::V1_4));
+        assert!(!ScriptHashUnsubscribe.is_available(ProtocolVersion::V1_4));
+        assert!(!ScriptHashUnsubscribe.is_available(ProtocolVersion::V1_4_1));
+        assert!(ScriptHashUnsubscribe.is_available(ProtocolVersion::V1_4_2));
+        assert!(RelayFee.is_available(ProtocolVersion::V1_5));
+        assert!(!RelayFee.is_available(ProtocolVersion::V1_6));
+        assert!(!TransactionFromPosition.is_available(ProtocolVersion::V1_3));
+        assert!(!OutpointSubscribe.is_available(ProtocolVersion::V1_5));
+        assert!(OutpointSubscribe.is_available(ProtocolVersion::V1_6));
+        assert!(MempoolGetInfo.is_available(ProtocolVersion::V1_6));
     }
 }
@@ -1,8 +1,19 @@
+pub mod call;
+pub mod capabilities;
 pub mod method;
+pub mod network;
 pub mod params;
 pub mod request;
 pub mod response;
+pub mod script;
+pub mod slices;
 pub mod types;
+pub mod verify;
+pub mod version;
+
+use method::Method;
+use miniscript::bitcoin::{BlockHash, Network, Txid};
+use types::{Id, StatusHash};
 
 #[derive(Debug)]
 pub enum Error {
@@ -10,7 +21,35 @@ pub enum Error {
     MethodNotFound,
     ResponseParsing(String),
     RawResponseParsing(String),
-    ResponseId(usize),
+    ResponseId(Id),
+    MissingResponse(Id),
     BatchParsing,
     WrongMethod,
+    ProtocolVersion(String),
+    UnsupportedMethod(Method),
+    UnsupportedParams(Method),
+    InvalidHeader(String),
+    InvalidHash(String),
+    InvalidProof,
+    MissingProof,
+    CheckpointMismatch,
+    HeaderNotChained(usize),
+    InvalidProofOfWork(BlockHash),
+    InvalidTransaction(String),
+    TxidMismatch {
+        expected: Txid,
+        got: Txid,
+    },
+    InvalidStatus(String),
+    InvalidAddress(String),
+    InvalidDescriptor(String),
+    StatusMismatch {
+        expected: Option<StatusHash>,
+        got: Option<StatusHash>,
+    },
+    /// The server is on another network, `got` is its genesis hash.
+    WrongNetwork {
+        expected: Network,
+        got: BlockHash,
+    },
 }
@@ -0,0 +1,109 @@
+use miniscript::bitcoin::{blockdata::constants::genesis_block, BlockHash, Network};
+
+/// A public server, the list is provided for convenience & may get outdated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownServer {
+    pub host: &'static str,
+    pub tcp_port: Option<u16>,
+    pub ssl_port: Option<u16>,
+}
+
+const fn server(host: &'static str, tcp_port: Option<u16>, ssl_port: Option<u16>) -> KnownServer {
+    KnownServer {
+        host,
+        tcp_port,
+        ssl_port,
+    }
+}
+
+const MAINNET_SERVERS: &[KnownServer] = &[
+    server("electrum.blockstream.info", Some(50001), Some(50002)),
+    server("electrum.emzy.de", Some(50001), Some(50002)),
+    server("bitcoin.aranguren.org", Some(50001), Some(50002)),
+    server("fulcrum.sethforprivacy.com", None, Some(50002)),
+];
+
+const TESTNET_SERVERS: &[KnownServer] = &[
+    server("electrum.blockstream.info", Some(60001), Some(60002)),
+    server("testnet.aranguren.org", Some(51001), Some(51002)),
+];
+
+const SIGNET_SERVERS: &[KnownServer] = &[server("mempool.space", None, Some(60602))];
+
+/// Well-known public servers of `network`.
+pub fn known_servers(network: Network) -> &'static [KnownServer] {
+    match network {
+        Network::Bitcoin => MAINNET_SERVERS,
+        Network::Testnet => TESTNET_SERVERS,
+        Network::Signet => SIGNET_SERVERS,
+        _ => &[],
+    }
+}
+
+/// Default TCP port of `network`, as used by electrs.
+pub fn default_tcp_port(network: Network) -> u16 {
+    match network {
+        Network::Bitcoin => 50001,
+        Network::Testnet => 60001,
+        Network::Signet => 60601,
+        Network::Regtest => 60401,
+        // NOTE: networks added upstream have no well-known port yet
+        _ => 50001,
+    }
+}
+
+/// Default SSL port of `network`, by convention the TCP port + 1.
+pub fn default_ssl_port(network: Network) -> u16 {
+    default_tcp_port(network) + 1
+}
+
+pub fn genesis_hash(network: Network) -> BlockHash {
+    genesis_block(network).block_hash()
+}
+
+/// The network whose genesis block is `genesis`, if any.
+pub fn from_genesis_hash(genesis: &BlockHash) -> Option<Network> {
+    [
+        Network::Bitcoin,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ]
+    .into_iter()
+    .find(|n| genesis_hash(*n) == *genesis)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use miniscript::bitcoin::hashes::Hash;
+
+    use super::*;
+
+    #[test]
+    fn genesis() {
+        let mainnet =
+            BlockHash::from_str("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f")
+                .unwrap();
+        assert_eq!(genesis_hash(Network::Bitcoin), mainnet);
+        assert_eq!(from_genesis_hash(&mainnet), Some(Network::Bitcoin));
+        let testnet = genesis_hash(Network::Testnet);
+        assert_eq!(from_genesis_hash(&testnet), Some(Network::Testnet));
+        assert_eq!(
+            from_genesis_hash(&BlockHash::from_byte_array([0; 32])),
+            None
+        );
+    }
+
+    #[test]
+    fn ports() {
+        assert_eq!(default_tcp_port(Network::Bitcoin), 50001);
+        assert_eq!(default_ssl_port(Network::Bitcoin), 50002);
+        assert_eq!(default_tcp_port(Network::Regtest), 60401);
+        for network in [Network::Bitcoin, Network::Testnet, Network::Signet] {
+            assert!(!known_servers(network).is_empty());
+        }
+        assert!(known_servers(Network::Regtest).is_empty());
+    }
+}
@@ -1,6 +1,9 @@
-use super::types::ScriptHash;
+use super::{method::Method, response::FeaturesResult, types::ScriptHash, Error};
 use miniscript::bitcoin::Txid;
-use miniscript::serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use miniscript::serde::{
+    de::DeserializeOwned, ser::SerializeSeq, Deserialize, Serialize, Serializer,
+};
+use serde_json::Value;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(untagged)]
@@ -30,17 +33,18 @@ impl From<&TxGetArgs> for (Txid, bool) {
 pub enum Params {
     #[serde(serialize_with = "default")]
     None,
+    BlockHeader((usize /* height*/,)),
     // NOTE: electrs does not support `cp_height` even if
-    // it's in the 1.4 version spec. ...
+    // it's in the 1.4 version spec.
     // https://electrumx.readthedocs.io/en/latest/protocol-methods.html#blockchain-block-header
-    // BlockHeader((usize /* height*/, usize /* cp_height */)),
-    BlockHeader((usize /* height*/,)),
+    BlockHeaderWithCheckpoint((usize /* height*/, usize /* cp_height */)),
+    BlockHeaders((usize /* start */, usize /* count */)),
     // NOTE: idem
-    BlockHeaders(
+    BlockHeadersWithCheckpoint(
         (
             usize, /* start */
             usize, /* count */
-                   // usize, /* cp_height */
+            usize, /* cp_height */
         ),
     ),
     TransactionBroadcast((String,)),
@@ -61,6 +65,110 @@ pub enum Params {
         ),
     ),
     Version((String, VersionKind)),
+    OutpointSubscribe((Txid, u32)),
+    OutpointUnsubscribe((Txid, u32)),
+    TransactionBroadcastPackage((Vec<String>,)),
+    AddPeer((FeaturesResult,)),
+    /// Params of a [`Method::Custom`], kept as is.
+    Custom(Value),
+}
+
+impl Params {
+    /// Returns the `cp_height` argument if any, a `cp_height` of 0
+    /// means no proof is requested.
+    pub fn checkpoint(&self) -> Option<usize> {
+        match self {
+            Params::BlockHeaderWithCheckpoint((_, cp_height))
+            | Params::BlockHeadersWithCheckpoint((_, _, cp_height))
+                if *cp_height > 0 =>
+            {
+                Some(*cp_height)
+            }
+            _ => None,
+        }
+    }
+}
+
+// NOTE: `null` or missing params are handled as an empty list
+fn param_list(params: Value) -> Result<Vec<Value>, Error> {
+    match params {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(params) => Ok(params),
+        _ => Err(Error::InvalidParam),
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Vec<Value>) -> Result<T, Error> {
+    serde_json::from_value(Value::Array(params)).map_err(|_| Error::InvalidParam)
+}
+
+impl Params {
+    /// Parse the params of a request, as the params are positional the
+    /// expected variant is chosen according to `method`.
+    pub fn from_value(method: &Method, params: Value) -> Result<Self, Error> {
+        if let Method::Custom(_) = method {
+            return Ok(Params::Custom(params));
+        }
+        let params = param_list(params)?;
+        let len = params.len();
+        Ok(match method {
+            Method::Banner
+            | Method::Donation
+            | Method::Features
+            | Method::HeadersSubscribe
+            | Method::FeeHistogram
+            | Method::ListPeers
+            | Method::Ping
+            | Method::RelayFee
+            | Method::MempoolGetInfo => {
+                if len != 0 {
+                    return Err(Error::InvalidParam);
+                }
+                Params::None
+            }
+            Method::BlockHeader if len == 2 => Params::BlockHeaderWithCheckpoint(parse(params)?),
+            Method::BlockHeader => Params::BlockHeader(parse(params)?),
+            Method::BlockHeaders if len == 3 => Params::BlockHeadersWithCheckpoint(parse(params)?),
+            Method::BlockHeaders => Params::BlockHeaders(parse(params)?),
+            Method::TransactionBroadcast => Params::TransactionBroadcast(parse(params)?),
+            Method::EstimateFee => Params::EstimateFee(parse(params)?),
+            Method::AddPeer => Params::AddPeer(parse(params)?),
+            Method::ScriptHashGetBalance => Params::ScriptHashGetBalance(parse(params)?),
+            Method::ScriptHashGetHistory => Params::ScriptHashGetHistory(parse(params)?),
+            Method::ScriptHashGetMempool => Params::ScriptHashGetMempool(parse(params)?),
+            Method::ScriptHashListUnspent => Params::ScriptHashListUnspent(parse(params)?),
+            Method::ScriptHashSubscribe => Params::ScriptHashSubscribe(parse(params)?),
+            Method::ScriptHashUnsubscribe => Params::ScriptHashUnsubscribe(parse(params)?),
+            Method::TransactionGet if len == 2 => {
+                let (txid, verbose) = parse(params)?;
+                Params::TransactionGet(TxGetArgs::TxidVerbose(txid, verbose))
+            }
+            Method::TransactionGet => Params::TransactionGet(TxGetArgs::Txid(parse(params)?)),
+            Method::TransactionGetMerkle => Params::TransactionGetMerkle(parse(params)?),
+            // NOTE: `merkle` defaults to false
+            Method::TransactionFromPosition if len == 2 => {
+                let (height, tx_pos) = parse(params)?;
+                Params::TransactionFromPosition((height, tx_pos, false))
+            }
+            Method::TransactionFromPosition => Params::TransactionFromPosition(parse(params)?),
+            // NOTE: `client_name` defaults to "" & `protocol_version` to "1.4"
+            Method::Version if len < 2 => {
+                let mut params = params.into_iter();
+                let name = match params.next() {
+                    Some(name) => parse::<(String,)>(vec![name])?.0,
+                    None => String::new(),
+                };
+                Params::Version((name, VersionKind::Single("1.4".into())))
+            }
+            Method::Version => Params::Version(parse(params)?),
+            Method::OutpointSubscribe => Params::OutpointSubscribe(parse(params)?),
+            Method::OutpointUnsubscribe => Params::OutpointUnsubscribe(parse(params)?),
+            Method::TransactionBroadcastPackage => {
+                Params::TransactionBroadcastPackage(parse(params)?)
+            }
+            Method::Custom(_) => unreachable!("handled above"),
+        })
+    }
 }
 
 fn default<S>(serializer: S) -> Result<S::Ok, S::Error>
@@ -146,6 +254,27 @@ mod tests {
         assert!(verbose);
     }
 
+    #[test]
+    fn version_defaults() {
+        let default =
+            |name: &str| Params::Version((name.into(), VersionKind::Single("1.4".into())));
+        let parse = |params| Params::from_value(&Method::Version, params);
+        assert_eq!(parse(serde_json::json!([])).unwrap(), default(""));
+        assert_eq!(parse(Value::Null).unwrap(), default(""));
+        assert_eq!(
+            parse(serde_json::json!(["client"])).unwrap(),
+            default("client")
+        );
+        assert_eq!(
+            parse(serde_json::json!(["client", ["1.2", "1.4"]])).unwrap(),
+            Params::Version((
+                "client".into(),
+                VersionKind::MinMax("1.2".into(), "1.4".into())
+            ))
+        );
+        assert!(parse(serde_json::json!([1])).is_err());
+    }
+
     #[test]
     fn version_kind() {
         let version1 = VersionKind::Single("1.4".into());
@@ -160,6 +289,11 @@ mod tests {
         json!(Params::None, "[]");
         json!(Params::BlockHeader((12,)), "[12]");
         json!(Params::BlockHeaders((12, 34)), "[12,34]");
+        json!(Params::BlockHeaderWithCheckpoint((12, 34)), "[12,34]");
+        json!(
+            Params::BlockHeadersWithCheckpoint((12, 34, 56)),
+            "[12,34,56]"
+        );
         json!(Params::TransactionBroadcast(("toto".into(),)), "[\"toto\"]");
         json!(Params::EstimateFee((2,)), "[2]");
 
@@ -219,5 +353,17 @@ mod tests {
             Params::Version(("last".into(), VersionKind::Single("1.4.into())".into()))),
             "[\"last\",\"1.4.into())\"]"
         );
+        json!(
+            Params::OutpointSubscribe((outpoint.txid, 42)),
+            "[\"5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456\",42]"
+        );
+        json!(
+            Params::OutpointUnsubscribe((outpoint.txid, 42)),
+            "[\"5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456\",42]"
+        );
+        json!(
+            Params::TransactionBroadcastPackage((vec!["toto".into(), "titi".into()],)),
+            "[[\"toto\",\"titi\"]]"
+        );
     }
 }
@@ -1,32 +1,68 @@
 use super::{
-    method::Method,
+    method::{CustomMethod, Method},
     params::{Params, TxGetArgs, VersionKind},
-    types::ScriptHash,
+    response::FeaturesResult,
+    script::{derive_scripts, ToScriptHash},
+    types::{Id, ScriptHash},
+    Error,
 };
-use miniscript::bitcoin::{Script, Txid};
-use serde::Serialize;
+use miniscript::{
+    bitcoin::{OutPoint, Txid},
+    Descriptor, DescriptorPublicKey,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::ops::Range;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub struct Request {
     jsonrpc: String,
-    pub id: usize,
+    pub id: Id,
     pub method: Method,
 
     #[serde(default)]
     params: Params,
 }
 
+#[derive(Deserialize)]
+struct RawRequest {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    id: Id,
+    method: Method,
+    #[serde(default)]
+    params: Value,
+}
+
+impl<'de> Deserialize<'de> for Request {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawRequest::deserialize(deserializer)?;
+        let params = Params::from_value(&raw.method, raw.params).map_err(|_| {
+            serde::de::Error::custom(format!("invalid params for {:?}", raw.method))
+        })?;
+        Ok(Request {
+            jsonrpc: raw.jsonrpc.unwrap_or_else(|| "2.0".into()),
+            id: raw.id,
+            method: raw.method,
+            params,
+        })
+    }
+}
+
 impl Request {
     fn new(method: Method, params: Params) -> Self {
         Request {
             jsonrpc: "2.0".into(),
-            id: 0,
+            id: Id::Number(0),
             method,
             params,
         }
     }
 
-    fn new_with_id(id: usize, method: Method, params: Params) -> Self {
+    fn new_with_id(id: Id, method: Method, params: Params) -> Self {
         Request {
             jsonrpc: "2.0".into(),
             id,
@@ -35,11 +71,15 @@ impl Request {
         }
     }
 
-    pub fn id(mut self, id: usize) -> Self {
-        self.id = id;
+    pub fn id(mut self, id: impl Into<Id>) -> Self {
+        self.id = id.into();
         self
     }
 
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
     pub fn ping() -> Self {
         Self::new(Method::Ping, Params::None)
     }
@@ -74,14 +114,32 @@ impl Request {
         Self::new(Method::ListPeers, Params::None)
     }
 
+    pub fn add_peer(features: FeaturesResult) -> Self {
+        Self::new(Method::AddPeer, Params::AddPeer((features,)))
+    }
+
     pub fn header(height: usize) -> Self {
         Self::new(Method::BlockHeader, Params::BlockHeader((height,)))
     }
 
+    pub fn header_with_checkpoint(height: usize, cp_height: usize) -> Self {
+        Self::new(
+            Method::BlockHeader,
+            Params::BlockHeaderWithCheckpoint((height, cp_height)),
+        )
+    }
+
     pub fn headers(start: usize, count: usize) -> Self {
         Self::new(Method::BlockHeaders, Params::BlockHeaders((start, count)))
     }
 
+    pub fn headers_with_checkpoint(start: usize, count: usize, cp_height: usize) -> Self {
+        Self::new(
+            Method::BlockHeaders,
+            Params::BlockHeadersWithCheckpoint((start, count, cp_height)),
+        )
+    }
+
     pub fn estimate_fee(block_target: u16) -> Self {
         Self::new(Method::EstimateFee, Params::EstimateFee((block_target,)))
     }
@@ -94,55 +152,67 @@ impl Request {
         Self::new(Method::RelayFee, Params::None)
     }
 
-    pub fn sh_get_balance(script: &Script) -> Self {
-        let sh = ScriptHash::new(script);
+    pub fn sh_get_balance<S: ToScriptHash>(script: S) -> Self {
+        let sh = script.to_script_hash();
         Self::new(
             Method::ScriptHashGetBalance,
             Params::ScriptHashGetBalance((sh,)),
         )
     }
 
-    pub fn sh_get_history(script: &Script) -> Self {
-        let sh = ScriptHash::new(script);
+    pub fn sh_get_history<S: ToScriptHash>(script: S) -> Self {
+        let sh = script.to_script_hash();
         Self::new(
             Method::ScriptHashGetHistory,
             Params::ScriptHashGetHistory((sh,)),
         )
     }
 
-    // NOTE: not supported by electrs
-    // pub fn sh_get_mempool(script: &Script) -> Self {
-    //     let sh = ScriptHash::new(script);
-    //     Self::new(
-    //         Method::ScriptHashGetMempool,
-    //         Params::ScriptHashGetMempool((sh,)),
-    //     )
-    // }
+    pub fn sh_get_mempool<S: ToScriptHash>(script: S) -> Self {
+        let sh = script.to_script_hash();
+        Self::new(
+            Method::ScriptHashGetMempool,
+            Params::ScriptHashGetMempool((sh,)),
+        )
+    }
 
-    pub fn sh_list_unspent(script: &Script) -> Self {
-        let sh = ScriptHash::new(script);
+    pub fn sh_list_unspent<S: ToScriptHash>(script: S) -> Self {
+        let sh = script.to_script_hash();
         Self::new(
             Method::ScriptHashListUnspent,
             Params::ScriptHashListUnspent((sh,)),
         )
     }
 
-    pub fn subscribe_sh(script: &Script) -> Self {
-        let sh = ScriptHash::new(script);
+    pub fn subscribe_sh<S: ToScriptHash>(script: S) -> Self {
+        let sh = script.to_script_hash();
         Self::new(
             Method::ScriptHashSubscribe,
             Params::ScriptHashSubscribe((sh,)),
         )
     }
 
-    pub fn unsubscribe_sh(script: &Script) -> Self {
-        let sh = ScriptHash::new(script);
+    pub fn unsubscribe_sh<S: ToScriptHash>(script: S) -> Self {
+        let sh = script.to_script_hash();
         Self::new(
             Method::ScriptHashUnsubscribe,
             Params::ScriptHashUnsubscribe((sh,)),
         )
     }
 
+    /// Build one request per script derived from `descriptor` over `range`
+    /// w/ one of the script hash constructors, e.g. `Request::subscribe_sh`.
+    pub fn for_descriptor(
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        range: Range<u32>,
+        constructor: impl Fn(ScriptHash) -> Request,
+    ) -> Result<Vec<Self>, Error> {
+        Ok(derive_scripts(descriptor, range)?
+            .iter()
+            .map(|(_, script)| constructor(script.to_script_hash()))
+            .collect())
+    }
+
     pub fn tx_broadcast(tx: String) -> Self {
         Self::new(
             Method::TransactionBroadcast,
@@ -181,6 +251,47 @@ impl Request {
     pub fn get_fee_histogram() -> Self {
         Self::new(Method::FeeHistogram, Params::None)
     }
+
+    pub fn subscribe_outpoint(outpoint: OutPoint) -> Self {
+        Self::new(
+            Method::OutpointSubscribe,
+            Params::OutpointSubscribe((outpoint.txid, outpoint.vout)),
+        )
+    }
+
+    pub fn unsubscribe_outpoint(outpoint: OutPoint) -> Self {
+        Self::new(
+            Method::OutpointUnsubscribe,
+            Params::OutpointUnsubscribe((outpoint.txid, outpoint.vout)),
+        )
+    }
+
+    pub fn mempool_info() -> Self {
+        Self::new(Method::MempoolGetInfo, Params::None)
+    }
+
+    pub fn tx_broadcast_package(txs: Vec<String>) -> Self {
+        Self::new(
+            Method::TransactionBroadcastPackage,
+            Params::TransactionBroadcastPackage((txs,)),
+        )
+    }
+
+    /// Build a request for the custom method `M`.
+    pub fn custom<M: CustomMethod>(params: &M::Params) -> Result<Self, Error> {
+        let params = serde_json::to_value(params).map_err(|_| Error::InvalidParam)?;
+        Ok(Self::custom_raw(M::NAME, params))
+    }
+
+    /// Build a request for an arbitrary method, `params` are sent as is
+    /// (an empty list if null).
+    pub fn custom_raw(method: &str, params: Value) -> Self {
+        let params = match params {
+            Value::Null => Value::Array(Vec::new()),
+            p => p,
+        };
+        Self::new(Method::Custom(method.into()), Params::Custom(params))
+    }
 }
 
 impl From<Request> for String {
@@ -193,7 +304,7 @@ impl From<Request> for String {
 mod tests {
     use std::str::FromStr;
 
-    use miniscript::bitcoin::OutPoint;
+    use miniscript::bitcoin::Script;
 
     use super::*;
     #[test]
@@ -223,6 +334,15 @@ mod tests {
             r#"{"jsonrpc":"2.0","id":0,"method":"server.peers.subscribe","params":[]}"#
         );
 
+        let features: FeaturesResult = serde_json::from_str(
+            r#"{"genesis_hash":"abc","hash_function":"sha256","hosts":{"tcp_port":50001},"protocol_max":"1.4","protocol_min":"1.4","pruning":null,"server_version":"toto"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            &serde_json::to_string(&Request::add_peer(features)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"server.add_peer","params":[{"genesis_hash":"abc","hosts":{"tcp_port":50001},"protocol_max":"1.4","protocol_min":"1.4","pruning":null,"server_version":"toto","hash_function":"sha256"}]}"#
+        );
+
         assert_eq!(
             &serde_json::to_string(&Request::version("smart".into(), "1.4".into())).unwrap(),
             r#"{"jsonrpc":"2.0","id":0,"method":"server.version","params":["smart","1.4"]}"#
@@ -248,6 +368,16 @@ mod tests {
             r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.block.headers","params":[12345,5]}"#
         );
 
+        assert_eq!(
+            &serde_json::to_string(&Request::header_with_checkpoint(12345, 20000)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.block.header","params":[12345,20000]}"#
+        );
+
+        assert_eq!(
+            &serde_json::to_string(&Request::headers_with_checkpoint(12345, 5, 20000)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.block.headers","params":[12345,5,20000]}"#
+        );
+
         assert_eq!(
             &serde_json::to_string(&Request::estimate_fee(5)).unwrap(),
             r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.estimatefee","params":[5]}"#
@@ -266,11 +396,10 @@ mod tests {
             &serde_json::to_string(&Request::sh_get_history(script)).unwrap(),
             r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.scripthash.get_history","params":["1da0af1706a31185763837b33f1d90782c0a78bbe644a59c987ab3ff9c0b346e"]}"#
         );
-        // NOTE: not supported by electrs
-        // assert_eq!(
-        //     &serde_json::to_string(&Request::sh_get_mempool(script)).unwrap(),
-        //     r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.scripthash.get_mempool","params":["1da0af1706a31185763837b33f1d90782c0a78bbe644a59c987ab3ff9c0b346e"]}"#
-        // );
+        assert_eq!(
+            &serde_json::to_string(&Request::sh_get_mempool(script)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.scripthash.get_mempool","params":["1da0af1706a31185763837b33f1d90782c0a78bbe644a59c987ab3ff9c0b346e"]}"#
+        );
         assert_eq!(
             &serde_json::to_string(&Request::sh_list_unspent(script)).unwrap(),
             r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.scripthash.listunspent","params":["1da0af1706a31185763837b33f1d90782c0a78bbe644a59c987ab3ff9c0b346e"]}"#
@@ -308,6 +437,26 @@ mod tests {
             &serde_json::to_string(&Request::get_fee_histogram()).unwrap(),
             r#"{"jsonrpc":"2.0","id":0,"method":"mempool.get_fee_histogram","params":[]}"#
         );
+        assert_eq!(
+            &serde_json::to_string(&Request::subscribe_outpoint(outpoint)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.outpoint.subscribe","params":["5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456",42]}"#
+        );
+        assert_eq!(
+            &serde_json::to_string(&Request::unsubscribe_outpoint(outpoint)).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.outpoint.unsubscribe","params":["5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456",42]}"#
+        );
+        assert_eq!(
+            &serde_json::to_string(&Request::mempool_info()).unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"mempool.get_info","params":[]}"#
+        );
+        assert_eq!(
+            &serde_json::to_string(&Request::tx_broadcast_package(vec![
+                "0200".into(),
+                "0201".into()
+            ]))
+            .unwrap(),
+            r#"{"jsonrpc":"2.0","id":0,"method":"blockchain.transaction.broadcast_package","params":[["0200","0201"]]}"#
+        );
     }
 
     #[test]
@@ -316,7 +465,7 @@ mod tests {
         let request = Request::ping();
         for i in 1..12usize {
             let mut r = request.clone();
-            r.id = i;
+            r.id = Id::Number(i);
             batch.push(r);
         }
 
@@ -325,4 +474,76 @@ mod tests {
 
         assert_eq!(&str_req, expected);
     }
+
+    #[test]
+    fn deserialize_request() {
+        let script = Script::from_bytes(&[0x00]);
+        let txid =
+            Txid::from_str("9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681")
+                .unwrap();
+        let features: FeaturesResult = serde_json::from_str(r#"{"hosts": {}, "pruning": null, "server_version": "ElectrumX 1.15.0", "protocol_min": "1.4", "protocol_max": "1.4.2", "genesis_hash": "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f", "hash_function": "sha256"}"#).unwrap();
+        let requests = vec![
+            Request::version("toto".into(), "1.4".into()),
+            Request::version_range("toto".into(), "1.4".into(), "1.6".into()),
+            Request::banner(),
+            Request::donation(),
+            Request::features(),
+            Request::add_peer(features),
+            Request::subscribe_peers(),
+            Request::ping(),
+            Request::header(1),
+            Request::header_with_checkpoint(1, 2),
+            Request::headers(1, 2),
+            Request::headers_with_checkpoint(1, 2, 3),
+            Request::estimate_fee(2),
+            Request::subscribe_headers(),
+            Request::relay_fee(),
+            Request::sh_get_balance(script),
+            Request::sh_get_history(script),
+            Request::sh_list_unspent(script),
+            Request::subscribe_sh(script),
+            Request::unsubscribe_sh(script),
+            Request::tx_broadcast("0200".into()),
+            Request::tx_get(txid),
+            Request::tx_get_verbose(txid),
+            Request::tx_get_merkle(txid, 3),
+            Request::tx_from_pos(1, 2, true),
+            Request::get_fee_histogram(),
+            Request::subscribe_outpoint(OutPoint::new(txid, 1)),
+            Request::unsubscribe_outpoint(OutPoint::new(txid, 1)),
+            Request::mempool_info(),
+            Request::tx_broadcast_package(vec!["0200".into()]),
+        ];
+        for request in requests {
+            let json = serde_json::to_string(&request).unwrap();
+            let parsed: Request = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, request, "{}", json);
+        }
+
+        // optional fields
+        let parsed: Request = serde_json::from_str(r#"{"id":3,"method":"server.ping"}"#).unwrap();
+        assert_eq!(parsed, Request::ping().id(3));
+        let parsed: Request = serde_json::from_str(
+            r#"{"id":0,"method":"blockchain.transaction.id_from_pos","params":[1,2]}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed, Request::tx_from_pos(1, 2, false));
+
+        // invalid params
+        for json in [
+            r#"{"id":0,"method":"server.ping","params":[1]}"#,
+            r#"{"id":0,"method":"blockchain.block.header","params":["a"]}"#,
+            r#"{"id":0,"method":"blockchain.scripthash.get_balance","params":{"scripthash":"00"}}"#,
+        ] {
+            assert!(serde_json::from_str::<Request>(json).is_err(), "{}", json);
+        }
+
+        // unknown methods are custom methods
+        let parsed: Request =
+            serde_json::from_str(r#"{"id":0,"method":"unknown","params":{"a":1}}"#).unwrap();
+        assert_eq!(
+            parsed,
+            Request::custom_raw("unknown", serde_json::json!({"a": 1}))
+        );
+    }
 }
@@ -1,9 +1,27 @@
-use std::{collections::HashMap, str::FromStr};
-
-use super::{method::Method, params::VersionKind, request::Request, types::ScriptHash, Error};
-use miniscript::bitcoin::Txid;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::{cmp::Ordering, collections::HashMap, fmt::Display, str::FromStr};
+
+use super::{
+    method::{CustomMethod, Method},
+    network,
+    params::VersionKind,
+    request::Request,
+    slices::{HeaderSlice, SliceDecoder, Visitor},
+    types::{Id, ScriptHash, StatusHash, HEADER_ROW_SIZE},
+    verify::{verify_header_chain, verify_tx_inclusion, Checkpoint},
+    version::ProtocolVersion,
+    Error,
+};
+use miniscript::bitcoin::{
+    address::NetworkUnchecked,
+    amount,
+    block::Header as BlockHeader,
+    consensus::deserialize,
+    hashes::{hex::FromHex, sha256d},
+    Address, Amount, BlockHash, FeeRate, Network, OutPoint, ScriptBuf, SignedAmount, Transaction,
+    TxMerkleNode, Txid, Weight, Wtxid,
+};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use serde_json::{value::RawValue, Value};
 
 #[derive(Debug, PartialEq)]
 pub enum Response {
@@ -13,7 +31,9 @@ pub enum Response {
     Ping(PingResponse),
     Banner(BannerResponse),
     Header(HeaderResponse),
+    HeaderWithProof(HeaderWithProofResponse),
     Headers(HeadersResponse),
+    HeadersWithProof(HeadersWithProofResponse),
     Version(VersionResponse),
     TxGet(TxGetResponse),
     SHSubscribe(SHSubscribeResponse),
@@ -32,6 +52,97 @@ pub enum Response {
     TxGetMerkle(TxGetMerkleResponse),
     TxFromposition(TxFromPositionResponse),
     ListPeers(ListPeersResponse),
+    AddPeer(AddPeerResponse),
+    OutpointSubscribe(OutpointSubscribeResponse),
+    OutpointUnsubscribe(OutpointUnsubscribeResponse),
+    OutpointNotification(OutpointNotification),
+    MempoolInfo(MempoolInfoResponse),
+    TxBroadcastPackage(TxBroadcastPackageResponse),
+    Custom(CustomResponse),
+    Unknown(UnknownMessage),
+}
+
+/// How the messages matching none of the modelled responses & notifications
+/// are handled, e.g. unsolicited notifications or responses to unknown ids.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Fail w/ a parsing error.
+    #[default]
+    Strict,
+    /// Return them as [`Response::Unknown`].
+    Lenient,
+    /// Log & drop them.
+    Skip,
+}
+
+/// A message matching none of the modelled responses & notifications.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownMessage {
+    pub method: Option<String>,
+    pub id: Option<Id>,
+    pub value: Value,
+}
+
+impl Response {
+    fn to_value(&self) -> Result<Value, serde_json::Error> {
+        match self {
+            Response::HeaderNotif(r) => serde_json::to_value(r),
+            Response::BatchHeaderNotif(r) => serde_json::to_value(r),
+            Response::SHNotification(r) => serde_json::to_value(r),
+            Response::Ping(r) => serde_json::to_value(r),
+            Response::Banner(r) => serde_json::to_value(r),
+            Response::Header(r) => serde_json::to_value(r),
+            Response::HeaderWithProof(r) => serde_json::to_value(r),
+            Response::Headers(r) => serde_json::to_value(r),
+            Response::HeadersWithProof(r) => serde_json::to_value(r),
+            Response::Version(r) => serde_json::to_value(r),
+            Response::TxGet(r) => serde_json::to_value(r),
+            Response::SHSubscribe(r) => serde_json::to_value(r),
+            Response::SHUnsubscribe(r) => serde_json::to_value(r),
+            Response::SHGetBalance(r) => serde_json::to_value(r),
+            Response::SHGetHistory(r) => serde_json::to_value(r),
+            Response::SHGetMempool(r) => serde_json::to_value(r),
+            Response::SHListUnspent(r) => serde_json::to_value(r),
+            Response::Error(r) => serde_json::to_value(r),
+            Response::Features(r) => serde_json::to_value(r),
+            Response::TxBroadcast(r) => serde_json::to_value(r),
+            Response::Donation(r) => serde_json::to_value(r),
+            Response::EstimateFee(r) => serde_json::to_value(r),
+            Response::FeeHistogram(r) => serde_json::to_value(r),
+            Response::RelayFee(r) => serde_json::to_value(r),
+            Response::TxGetMerkle(r) => serde_json::to_value(r),
+            Response::TxFromposition(r) => serde_json::to_value(r),
+            Response::ListPeers(r) => serde_json::to_value(r),
+            Response::AddPeer(r) => serde_json::to_value(r),
+            Response::OutpointSubscribe(r) => serde_json::to_value(r),
+            Response::OutpointUnsubscribe(r) => serde_json::to_value(r),
+            Response::OutpointNotification(r) => serde_json::to_value(r),
+            Response::MempoolInfo(r) => serde_json::to_value(r),
+            Response::TxBroadcastPackage(r) => serde_json::to_value(r),
+            Response::Custom(r) => serde_json::to_value(r),
+            Response::Unknown(r) => Ok(r.value.clone()),
+        }
+    }
+}
+
+/// Serialize to the wire format, `jsonrpc` is added to every message.
+impl Serialize for Response {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.to_value().map_err(serde::ser::Error::custom)? {
+            Value::Object(map) => {
+                let mut s = serializer.serialize_map(Some(map.len() + 1))?;
+                s.serialize_entry("jsonrpc", "2.0")?;
+                for (k, v) in &map {
+                    s.serialize_entry(k, v)?;
+                }
+                s.end()
+            }
+            value => value.serialize(serializer),
+        }
+    }
 }
 
 impl From<Response> for Vec<Response> {
@@ -40,37 +151,206 @@ impl From<Response> for Vec<Response> {
     }
 }
 
+impl Response {
+    /// Returns the id of the request this response answers, `None` for notifications.
+    pub fn id(&self) -> Option<Id> {
+        match self {
+            Response::HeaderNotif(HeaderNotification::Single(r)) => Some(r.id.clone()),
+            Response::HeaderNotif(HeaderNotification::Batch(_))
+            | Response::BatchHeaderNotif(_)
+            | Response::SHNotification(_)
+            | Response::OutpointNotification(_) => None,
+            Response::Ping(r) => Some(r.id.clone()),
+            Response::Banner(r) => Some(r.id.clone()),
+            Response::Header(r) => Some(r.id.clone()),
+            Response::Headers(r) => Some(r.id.clone()),
+            Response::HeaderWithProof(r) => Some(r.id.clone()),
+            Response::HeadersWithProof(r) => Some(r.id.clone()),
+            Response::Version(r) => Some(r.id.clone()),
+            Response::TxGet(r) => Some(r.id.clone()),
+            Response::SHSubscribe(r) => Some(r.id.clone()),
+            Response::SHUnsubscribe(r) => Some(r.id.clone()),
+            Response::SHGetBalance(r) => Some(r.id.clone()),
+            Response::SHGetHistory(r) => Some(r.id.clone()),
+            Response::SHGetMempool(r) => Some(r.id.clone()),
+            Response::SHListUnspent(r) => Some(r.id.clone()),
+            Response::Error(r) => r.id.clone(),
+            Response::Features(r) => Some(r.id.clone()),
+            Response::TxBroadcast(r) => Some(r.id.clone()),
+            Response::Donation(r) => Some(r.id.clone()),
+            Response::EstimateFee(r) => Some(r.id.clone()),
+            Response::FeeHistogram(r) => Some(r.id.clone()),
+            Response::RelayFee(r) => Some(r.id.clone()),
+            Response::TxGetMerkle(r) => Some(r.id.clone()),
+            Response::TxFromposition(r) => Some(r.id.clone()),
+            Response::ListPeers(r) => Some(r.id.clone()),
+            Response::AddPeer(r) => Some(r.id.clone()),
+            Response::OutpointSubscribe(r) => Some(r.id.clone()),
+            Response::OutpointUnsubscribe(r) => Some(r.id.clone()),
+            Response::MempoolInfo(r) => Some(r.id.clone()),
+            Response::TxBroadcastPackage(r) => Some(r.id.clone()),
+            Response::Custom(r) => Some(r.id.clone()),
+            // NOTE: the id of an unknown message does not match any request
+            Response::Unknown(_) => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(transparent)]
 pub struct ResponseBatch {
     pub batch: Vec<Response>,
 }
 
+/// Parse a message or a batch, a batch element failing to parse does not
+/// fail the other elements.
 pub fn parse_str_response(
     raw: &str,
-    index: &HashMap<usize, Request>,
-) -> Result<Vec<Response>, Error> {
-    // first we check if it's a batch
-    let batch = ResponseBatch::from_str(raw, index);
-    if let Ok(b) = batch {
-        return Ok(b.batch);
+    index: &HashMap<Id, Request>,
+) -> Result<Vec<Result<Response, Error>>, Error> {
+    parse_str_response_with(raw, index, Strictness::Strict)
+}
+
+pub fn parse_str_response_with(
+    raw: &str,
+    index: &HashMap<Id, Request>,
+    strictness: Strictness,
+) -> Result<Vec<Result<Response, Error>>, Error> {
+    if raw.trim_start().starts_with('[') {
+        Ok(BatchResponses::parse_with(raw, index, strictness)?.into_results())
+    } else {
+        Ok(Response::try_parse_with(raw, index, strictness)?
+            .into_iter()
+            .map(Ok)
+            .collect())
     }
-    // then try to parse a single Response
-    Ok(Response::try_parse(raw, index)?.into())
 }
 
 impl ResponseBatch {
-    pub fn from_str(s: &str, index: &HashMap<usize, Request>) -> Result<Self, Error> {
-        let parsed: Result<Vec<Value>, _> = serde_json::from_str(s);
-        if let Ok(parsed) = parsed {
-            let mut batch = Vec::<Response>::new();
-            for request in parsed {
-                let raw = serde_json::to_string(&request).map_err(|_| Error::BatchParsing)?;
-                batch.push(Response::try_parse(&raw, index)?);
+    pub fn from_str(s: &str, index: &HashMap<Id, Request>) -> Result<Self, Error> {
+        Self::from_str_with(s, index, Strictness::Strict)
+    }
+
+    pub fn from_str_with(
+        s: &str,
+        index: &HashMap<Id, Request>,
+        strictness: Strictness,
+    ) -> Result<Self, Error> {
+        let batch = BatchResponses::parse_with(s, index, strictness)?
+            .into_results()
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        Ok(ResponseBatch { batch })
+    }
+}
+
+/// A batch response parsed element by element, a malformed element or an
+/// unexpected id does not fail the whole batch.
+#[derive(Debug, Default)]
+pub struct BatchResponses {
+    /// The response, or the parsing error, of each element by request id.
+    pub results: HashMap<Id, Result<Response, Error>>,
+    /// Ids received more than once, only the first element is kept.
+    pub duplicates: Vec<Id>,
+    /// Elements w/o id, w/ a null id or w/ an id missing from the index.
+    pub unknown: Vec<Result<Response, Error>>,
+    // ids of `results` in the order received
+    received: Vec<Id>,
+}
+
+#[derive(Deserialize)]
+struct IdOnly {
+    #[serde(default)]
+    id: Option<Id>,
+}
+
+impl BatchResponses {
+    pub fn parse(s: &str, index: &HashMap<Id, Request>) -> Result<Self, Error> {
+        Self::parse_with(s, index, Strictness::Strict)
+    }
+
+    /// Same as [`BatchResponses::parse()`] but the unknown messages are
+    /// handled according to `strictness`.
+    pub fn parse_with(
+        s: &str,
+        index: &HashMap<Id, Request>,
+        strictness: Strictness,
+    ) -> Result<Self, Error> {
+        // NOTE: elements are only borrowed as raw JSON here, they are
+        // deserialized once by `Response::try_parse_with()`
+        let raw: Vec<&RawValue> = serde_json::from_str(s).map_err(|_| Error::BatchParsing)?;
+        let mut batch = BatchResponses::default();
+        for r in raw {
+            let result = match Response::try_parse_with(r.get(), index, strictness) {
+                Ok(Some(response)) => Ok(response),
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            let id = match &result {
+                Ok(response) => response.id(),
+                Err(Error::ResponseId(_)) => None,
+                // NOTE: the id is only parsed again for malformed elements
+                Err(_) => serde_json::from_str::<IdOnly>(r.get())
+                    .ok()
+                    .and_then(|i| i.id),
+            };
+            match id.filter(|id| index.contains_key(id)) {
+                Some(id) if batch.results.contains_key(&id) => batch.duplicates.push(id),
+                Some(id) => {
+                    batch.received.push(id.clone());
+                    batch.results.insert(id, result);
+                }
+                None => batch.unknown.push(result),
             }
-            Ok(ResponseBatch { batch })
-        } else {
-            Err(Error::BatchParsing)
         }
+        Ok(batch)
+    }
+
+    /// Ids from `ids` w/ no element in the batch.
+    pub fn missing(&self, ids: &[Id]) -> Vec<Id> {
+        ids.iter()
+            .filter(|id| !self.results.contains_key(id))
+            .cloned()
+            .collect()
     }
+
+    /// Take the result of the request `id`, fails w/ [`Error::MissingResponse`]
+    /// if the batch has no element for it.
+    pub fn take(&mut self, id: &Id) -> Result<Response, Error> {
+        self.results
+            .remove(id)
+            .unwrap_or_else(|| Err(Error::MissingResponse(id.clone())))
+    }
+
+    /// All the results in the order received, followed by the elements w/o
+    /// known id, duplicates are dropped.
+    pub fn into_results(mut self) -> Vec<Result<Response, Error>> {
+        let mut results: Vec<_> = self
+            .received
+            .iter()
+            .filter_map(|id| self.results.remove(id))
+            .collect();
+        results.append(&mut self.unknown);
+        results
+    }
+
+    /// The results of the requests `ids`, in the same order.
+    pub fn into_ordered(mut self, ids: &[Id]) -> Vec<Result<Response, Error>> {
+        ids.iter().map(|id| self.take(id)).collect()
+    }
+}
+
+/// The keys used to choose the type of a message, other keys are skipped
+/// w/o being deserialized.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(default)]
+    id: Option<Id>,
+    #[serde(default)]
+    method: Option<Method>,
+    // NOTE: some servers send `"error": null` along w/ the result
+    #[serde(default, borrow)]
+    error: Option<&'a RawValue>,
 }
 
 macro_rules! parse {
@@ -82,39 +362,108 @@ macro_rules! parse {
 }
 
 impl Response {
-    pub fn parse(raw: &str, index: &HashMap<usize, Request>) -> Response {
+    pub fn parse(raw: &str, index: &HashMap<Id, Request>) -> Response {
         Self::try_parse(raw, index).unwrap()
     }
 
-    pub fn try_parse(raw: &str, index: &HashMap<usize, Request>) -> Result<Response, Error> {
-        // first we handle the case of a single error
-        let error: Result<ErrorResponse, _> = serde_json::from_str(raw);
-        if let Ok(e) = error {
-            return Ok(Response::Error(e));
+    /// Parse a single message, the type of the message is chosen from its
+    /// `error`, `method` & `id` keys then the message is deserialized
+    /// straight into this type.
+    pub fn try_parse(raw: &str, index: &HashMap<Id, Request>) -> Result<Response, Error> {
+        Self::parse_message(raw, index, false)
+    }
+
+    /// Same as [`Response::try_parse()`] but the messages matching none of
+    /// the modelled responses & notifications are handled according to
+    /// `strictness`, returns `None` if the message is skipped.
+    pub fn try_parse_with(
+        raw: &str,
+        index: &HashMap<Id, Request>,
+        strictness: Strictness,
+    ) -> Result<Option<Response>, Error> {
+        match (
+            strictness,
+            Self::parse_message(raw, index, strictness != Strictness::Strict)?,
+        ) {
+            (Strictness::Skip, Response::Unknown(m)) => {
+                log::warn!("Skip unknown message: {}", m.value);
+                Ok(None)
+            }
+            (_, response) => Ok(Some(response)),
         }
+    }
+
+    fn parse_message(
+        raw: &str,
+        index: &HashMap<Id, Request>,
+        lenient: bool,
+    ) -> Result<Response, Error> {
+        let envelope: Envelope = serde_json::from_str(raw)
+            .map_err(|e| Error::RawResponseParsing(format!("Fail to parse `{}`: {:?}", raw, e)))?;
+        let unknown = |method: Option<String>, error: Error| {
+            if !lenient {
+                return Err(error);
+            }
+            let value = serde_json::from_str(raw).map_err(|e| {
+                Error::RawResponseParsing(format!("Fail to parse `{}`: {:?}", raw, e))
+            })?;
+            Ok(Response::Unknown(UnknownMessage {
+                method,
+                id: envelope.id.clone(),
+                value,
+            }))
+        };
 
-        // then we handle the Batch Header Notification case
-        let header_notif: Result<BatchHeaderNotif, _> = serde_json::from_str(raw);
-        if let Ok(n) = header_notif {
-            return Ok(Response::BatchHeaderNotif(n));
+        if envelope.error.is_some() {
+            let mut e: ErrorResponse =
+                serde_json::from_str(raw).map_err(|_| Error::ResponseParsing(raw.into()))?;
+            e.method =
+                e.id.as_ref()
+                    .and_then(|id| index.get(id))
+                    .map(|r| r.method.clone());
+            return Ok(Response::Error(e));
         }
 
-        // then we handle the ScriptHash Notification case
-        let sh_notif: Result<SHNotification, _> = serde_json::from_str(raw);
-        if let Ok(n) = sh_notif {
-            return Ok(Response::SHNotification(n));
+        // notifications have a `method` but no `id`
+        if let Some(method) = &envelope.method {
+            return match method {
+                Method::HeadersSubscribe => parse!(BatchHeaderNotif, BatchHeaderNotif, raw),
+                Method::ScriptHashSubscribe => parse!(SHNotification, SHNotification, raw),
+                Method::OutpointSubscribe => {
+                    parse!(OutpointNotification, OutpointNotification, raw)
+                }
+                // NOTE: custom notifications are not dispatched, they are
+                // handled as unknown messages
+                m => unknown(Some(format!("{:?}", m)), Error::WrongMethod),
+            };
         }
 
-        // the we handle the case we need to match request/response id
-        let rr: RawResponse = serde_json::from_str(raw)
-            .map_err(|e| Error::RawResponseParsing(format!("Fail to parse `{}`: {:?}", raw, e)))?;
-        let request = index.get(&rr.id).ok_or(Error::ResponseId(rr.id))?;
+        // then we need to match request/response id
+        let id = match &envelope.id {
+            Some(id) => id,
+            None => {
+                return unknown(
+                    None,
+                    Error::RawResponseParsing(format!("Missing id: `{}`", raw)),
+                )
+            }
+        };
+        let request = match index.get(id) {
+            Some(request) => request,
+            None => return unknown(None, Error::ResponseId(id.clone())),
+        };
         match request.method {
             Method::Ping => parse!(Ping, PingResponse, raw),
             Method::Banner => parse!(Banner, BannerResponse, raw),
             Method::HeadersSubscribe => parse!(HeaderNotif, HeaderNotification, raw),
-            Method::BlockHeader => parse!(Header, HeaderResponse, raw),
-            Method::BlockHeaders => parse!(Headers, HeadersResponse, raw),
+            Method::BlockHeader => match request.params().checkpoint() {
+                Some(_) => parse!(HeaderWithProof, HeaderWithProofResponse, raw),
+                None => parse!(Header, HeaderResponse, raw),
+            },
+            Method::BlockHeaders => match request.params().checkpoint() {
+                Some(_) => parse!(HeadersWithProof, HeadersWithProofResponse, raw),
+                None => parse!(Headers, HeadersResponse, raw),
+            },
             Method::Version => parse!(Version, VersionResponse, raw),
             Method::TransactionGet => parse!(TxGet, TxGetResponse, raw),
             Method::ScriptHashSubscribe => parse!(SHSubscribe, SHSubscribeResponse, raw),
@@ -122,31 +471,153 @@ impl Response {
             Method::ScriptHashGetBalance => parse!(SHGetBalance, SHGetBalanceResponse, raw),
             Method::ScriptHashGetHistory => parse!(SHGetHistory, SHGetHistoryResponse, raw),
             Method::ScriptHashListUnspent => parse!(SHListUnspent, SHListUnspentResponse, raw),
-            // NOTE: not supported by electrs
-            // Method::ScriptHashGetMempool => parse!(SHGetMempool, SHGetMempoolResponse, raw),
+            Method::ScriptHashGetMempool => parse!(SHGetMempool, SHGetMempoolResponse, raw),
             Method::Features => parse!(Features, FeaturesResponse, raw),
             Method::Donation => parse!(Donation, DonationResponse, raw),
             Method::EstimateFee => parse!(EstimateFee, EstimateFeeResponse, raw),
             Method::FeeHistogram => parse!(FeeHistogram, FeeHistogramResponse, raw),
             Method::RelayFee => parse!(RelayFee, RelayFeeResponse, raw),
             Method::TransactionGetMerkle => parse!(TxGetMerkle, TxGetMerkleResponse, raw),
-            Method::TransactionFromPosition => parse!(TxFromposition, TxFromPositionResponse, raw),
+            Method::TransactionFromPosition => {
+                parse!(TxFromposition, TxFromPositionResponse, raw)
+            }
             Method::TransactionBroadcast => parse!(TxBroadcast, TxBroadcastResponse, raw),
-            Method::ListPeers => todo!(),
+            Method::ListPeers => parse!(ListPeers, ListPeersResponse, raw),
+            Method::AddPeer => parse!(AddPeer, AddPeerResponse, raw),
+            Method::OutpointSubscribe => {
+                parse!(OutpointSubscribe, OutpointSubscribeResponse, raw)
+            }
+            Method::OutpointUnsubscribe => {
+                parse!(OutpointUnsubscribe, OutpointUnsubscribeResponse, raw)
+            }
+            Method::MempoolGetInfo => parse!(MempoolInfo, MempoolInfoResponse, raw),
+            Method::TransactionBroadcastPackage => {
+                parse!(TxBroadcastPackage, TxBroadcastPackageResponse, raw)
+            }
+            Method::Custom(ref name) => {
+                let mut r: CustomResponse =
+                    serde_json::from_str(raw).map_err(|_| Error::ResponseParsing(raw.into()))?;
+                r.method = name.clone();
+                Ok(Response::Custom(r))
+            }
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Error codes defined by JSON-RPC 2.0 & used by Electrum servers, servers
+/// can also forward the (negative) error codes of bitcoind.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(from = "i64", into = "i64")]
+pub enum RpcErrorCode {
+    ParseError,
+    InvalidRequest,
+    MethodNotFound,
+    InvalidParams,
+    InternalError,
+    BadRequest,
+    DaemonError,
+    Other(i64),
+}
+
+impl From<i64> for RpcErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            -32700 => Self::ParseError,
+            -32600 => Self::InvalidRequest,
+            -32601 => Self::MethodNotFound,
+            -32602 => Self::InvalidParams,
+            -32603 => Self::InternalError,
+            1 => Self::BadRequest,
+            2 => Self::DaemonError,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<RpcErrorCode> for i64 {
+    fn from(code: RpcErrorCode) -> Self {
+        match code {
+            RpcErrorCode::ParseError => -32700,
+            RpcErrorCode::InvalidRequest => -32600,
+            RpcErrorCode::MethodNotFound => -32601,
+            RpcErrorCode::InvalidParams => -32602,
+            RpcErrorCode::InternalError => -32603,
+            RpcErrorCode::BadRequest => 1,
+            RpcErrorCode::DaemonError => 2,
+            RpcErrorCode::Other(code) => code,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct ErrorResult {
-    pub code: usize,
+    pub code: RpcErrorCode,
     pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl ErrorResult {
+    pub fn new(code: RpcErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn parse_error() -> Self {
+        Self::new(RpcErrorCode::ParseError, "parse error")
+    }
+
+    pub fn invalid_request() -> Self {
+        Self::new(RpcErrorCode::InvalidRequest, "invalid request")
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            RpcErrorCode::MethodNotFound,
+            format!("unknown method {}", method),
+        )
+    }
+
+    pub fn invalid_params(method: &Method) -> Self {
+        Self::new(
+            RpcErrorCode::InvalidParams,
+            format!("invalid params for {:?}", method),
+        )
+    }
+
+    pub fn unsupported(method: &Method) -> Self {
+        Self::new(
+            RpcErrorCode::BadRequest,
+            format!("{:?} is not supported", method),
+        )
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorCode::BadRequest, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorCode::InternalError, message)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct ErrorResponse {
-    pub id: usize,
+    // NOTE: `null` if the server could not read the id of the request,
+    // e.g. on parse errors
+    pub id: Option<Id>,
     pub error: ErrorResult,
+    /// The method of the request this error answers, if known.
+    #[serde(skip)]
+    pub method: Option<Method>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -156,6 +627,16 @@ pub struct SHNotification {
     pub status: (ScriptHash, Option<String>),
 }
 
+impl SHNotification {
+    pub fn script_hash(&self) -> ScriptHash {
+        self.status.0
+    }
+
+    pub fn status_hash(&self) -> Result<Option<StatusHash>, Error> {
+        parse_status(&self.status.1)
+    }
+}
+
 impl FromStr for SHNotification {
     type Err = Error;
     fn from_str(value: &str) -> Result<Self, Error> {
@@ -171,13 +652,15 @@ impl FromStr for SHNotification {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct RawResponse {
+    // NOTE: some servers omit it
+    #[serde(default)]
     jsonrpc: String,
-    pub id: usize,
+    pub id: Id,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct BannerResponse {
-    pub id: usize,
+    pub id: Id,
     pub result: String,
 }
 
@@ -188,9 +671,15 @@ pub struct Header {
     pub raw_header: String,
 }
 
+impl Header {
+    pub fn header(&self) -> Result<BlockHeader, Error> {
+        parse_header(&self.raw_header)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SingleHeaderNotif {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub header: Header,
 }
@@ -211,11 +700,17 @@ pub enum HeaderNotification {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct HeaderResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub raw_header: String,
 }
 
+impl HeaderResponse {
+    pub fn header(&self) -> Result<BlockHeader, Error> {
+        parse_header(&self.raw_header)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct Headers {
     pub count: usize,
@@ -224,42 +719,189 @@ pub struct Headers {
     pub max: usize,
 }
 
+impl Headers {
+    pub fn headers(&self) -> Result<Vec<BlockHeader>, Error> {
+        parse_headers(&self.raw_headers, self.count)
+    }
+
+    /// Walk the headers w/ `decoder` w/o building `BlockHeader`s.
+    pub fn visit<F>(&self, decoder: &mut SliceDecoder, f: F) -> Result<(), Error>
+    where
+        F: FnMut(&HeaderSlice<'_>),
+    {
+        if decoder.headers(&self.raw_headers, f)? != self.count {
+            return Err(Error::InvalidHeader(self.raw_headers.clone()));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct HeadersResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub headers: Headers,
 }
 
+fn parse_hashes(hashes: &[String]) -> Result<Vec<sha256d::Hash>, Error> {
+    hashes
+        .iter()
+        .map(|h| sha256d::Hash::from_str(h).map_err(|_| Error::InvalidHash(h.clone())))
+        .collect()
+}
+
+fn parse_header(raw: &str) -> Result<BlockHeader, Error> {
+    let bytes = Vec::<u8>::from_hex(raw).map_err(|_| Error::InvalidHeader(raw.into()))?;
+    deserialize(&bytes).map_err(|_| Error::InvalidHeader(raw.into()))
+}
+
+// Split `count` concatenated headers
+fn parse_headers(raw: &str, count: usize) -> Result<Vec<BlockHeader>, Error> {
+    let invalid = || Error::InvalidHeader(raw.into());
+    let bytes = Vec::<u8>::from_hex(raw).map_err(|_| invalid())?;
+    if count.checked_mul(HEADER_ROW_SIZE) != Some(bytes.len()) {
+        return Err(invalid());
+    }
+    bytes
+        .chunks_exact(HEADER_ROW_SIZE)
+        .map(|chunk| deserialize(chunk).map_err(|_| invalid()))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HeaderWithProof {
+    pub branch: Vec<String>,
+    #[serde(rename = "header")]
+    pub raw_header: String,
+    pub root: String,
+}
+
+impl HeaderWithProof {
+    pub fn header(&self) -> Result<BlockHeader, Error> {
+        parse_header(&self.raw_header)
+    }
+
+    pub fn branch(&self) -> Result<Vec<sha256d::Hash>, Error> {
+        parse_hashes(&self.branch)
+    }
+
+    pub fn root(&self) -> Result<sha256d::Hash, Error> {
+        sha256d::Hash::from_str(&self.root).map_err(|_| Error::InvalidHash(self.root.clone()))
+    }
+
+    /// Verify that the header is at `height` in the chain committed by `checkpoint`.
+    pub fn verify(&self, height: usize, checkpoint: &Checkpoint) -> Result<BlockHeader, Error> {
+        let header = self.header()?;
+        checkpoint.verify(&header, height, &self.branch()?, &self.root()?)?;
+        Ok(header)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HeaderWithProofResponse {
+    pub id: Id,
+    #[serde(rename = "result")]
+    pub header: HeaderWithProof,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HeadersWithProof {
+    pub count: usize,
+    #[serde(rename = "hex")]
+    pub raw_headers: String,
+    pub max: usize,
+    // NOTE: the branch is the proof of the last header returned
+    pub branch: Vec<String>,
+    pub root: String,
+}
+
+impl HeadersWithProof {
+    pub fn headers(&self) -> Result<Vec<BlockHeader>, Error> {
+        parse_headers(&self.raw_headers, self.count)
+    }
+
+    pub fn branch(&self) -> Result<Vec<sha256d::Hash>, Error> {
+        parse_hashes(&self.branch)
+    }
+
+    pub fn root(&self) -> Result<sha256d::Hash, Error> {
+        sha256d::Hash::from_str(&self.root).map_err(|_| Error::InvalidHash(self.root.clone()))
+    }
+
+    /// Verify that the last header of the chunk starting at `start` is in
+    /// the chain committed by `checkpoint`, the previous headers are then
+    /// committed by their `prev_blockhash` (linkage & PoW are checked).
+    pub fn verify(&self, start: usize, checkpoint: &Checkpoint) -> Result<BlockHeader, Error> {
+        let mut headers = self.headers()?;
+        verify_header_chain(None, &headers)?;
+        let last = headers
+            .pop()
+            .ok_or(Error::InvalidHeader(self.raw_headers.clone()))?;
+        checkpoint.verify(
+            &last,
+            start + self.count - 1,
+            &self.branch()?,
+            &self.root()?,
+        )?;
+        Ok(last)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HeadersWithProofResponse {
+    pub id: Id,
+    #[serde(rename = "result")]
+    pub headers: HeadersWithProof,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct TxBroadcastResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub txid: Txid,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct DonationResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub address: Option<String>,
 }
 
+impl DonationResponse {
+    /// Parse the donation address and check it is valid for `network`, `None`
+    /// if the server has no donation address.
+    pub fn address(&self, network: Network) -> Option<Result<Address, Error>> {
+        self.address.as_deref().filter(|a| !a.is_empty()).map(|a| {
+            Address::<NetworkUnchecked>::from_str(a)
+                .ok()
+                .and_then(|addr| addr.require_network(network).ok())
+                .ok_or(Error::InvalidAddress(a.into()))
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct EstimateFeeResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub fee: OptionalFee,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+impl EstimateFeeResponse {
+    pub fn fee_rate(&self) -> Option<FeeRate> {
+        self.fee.fee_rate()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(untagged)]
 pub enum Port {
     String(String),
     U16(u16),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Host {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tcp_port: Option<Port>,
@@ -267,14 +909,14 @@ pub struct Host {
     pub ssl_port: Option<Port>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(untagged)]
 pub enum Hosts {
     Single(Host),
     Map(HashMap<String, Host>),
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct FeaturesResult {
     #[serde(rename = "genesis_hash")]
     pub genesis: String,
@@ -288,23 +930,59 @@ pub struct FeaturesResult {
     pub services: Option<Vec<String>>,
 }
 
+impl FeaturesResult {
+    pub fn genesis_hash(&self) -> Result<BlockHash, Error> {
+        BlockHash::from_str(&self.genesis).map_err(|_| Error::InvalidHash(self.genesis.clone()))
+    }
+
+    /// Check the server is on `network` by comparing genesis hashes.
+    pub fn check_network(&self, network: Network) -> Result<(), Error> {
+        let got = self.genesis_hash()?;
+        if got != network::genesis_hash(network) {
+            return Err(Error::WrongNetwork {
+                expected: network,
+                got,
+            });
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct FeaturesResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub features: FeaturesResult,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct FeeHistogramResponse {
-    pub id: usize,
+    pub id: Id,
+    // NOTE: (fee rate in sat/vB, vsize) pairs, some servers send fractional
+    // fee rates
     #[serde(rename = "result")]
-    pub histogram: Vec<(usize, usize)>,
+    pub histogram: Vec<(f64, u64)>,
+}
+
+impl FeeHistogramResponse {
+    /// The histogram as (fee rate, weight) pairs, w/o the entries whose size
+    /// does not fit a weight.
+    pub fn fee_rates(&self) -> Vec<(FeeRate, Weight)> {
+        self.histogram
+            .iter()
+            .filter_map(|(rate, vsize)| Some((sat_per_vb(*rate), Weight::from_vb(*vsize)?)))
+            .collect()
+    }
+}
+
+// 1 vB is 4 wu
+fn sat_per_vb(rate: f64) -> FeeRate {
+    FeeRate::from_sat_per_kwu((rate * 250.0).round() as u64)
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct PingResponse {
-    pub id: usize,
+    pub id: Id,
     // result should always be `null`
     pub result: Option<String>,
 }
@@ -312,122 +990,472 @@ pub struct PingResponse {
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum OptionalFee {
+    // NOTE: in BTC/kvB
     Fee(f64),
+    // NOTE: should be -1 if the server cannot estimate the fee, but
+    // actually parsed as `Fee(-1.0)`
     None(i64),
 }
 
+impl OptionalFee {
+    pub fn fee_rate(&self) -> Option<FeeRate> {
+        match self {
+            // 1 BTC/kvB is 1e8 sat/kvB or 1e8 / 4 sat/kwu
+            Self::Fee(btc_per_kvb) if *btc_per_kvb >= 0.0 => Some(FeeRate::from_sat_per_kwu(
+                (btc_per_kvb * 100_000_000.0 / 4.0).round() as u64,
+            )),
+            Self::Fee(_) | Self::None(_) => None,
+        }
+    }
+}
+
+impl From<Option<FeeRate>> for OptionalFee {
+    fn from(fee_rate: Option<FeeRate>) -> Self {
+        match fee_rate {
+            Some(rate) => Self::Fee(rate.to_sat_per_kwu() as f64 * 4.0 / 100_000_000.0),
+            None => Self::None(-1),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct RelayFeeResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
-    // TODO: handle
     pub fee: OptionalFee,
 }
 
+impl RelayFeeResponse {
+    pub fn fee_rate(&self) -> Option<FeeRate> {
+        self.fee.fee_rate()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SHSubscribeResponse {
-    pub id: usize,
+    pub id: Id,
     pub result: Option<String>,
 }
 
+impl SHSubscribeResponse {
+    pub fn status_hash(&self) -> Result<Option<StatusHash>, Error> {
+        parse_status(&self.result)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SHUnsubscribeResponse {
-    pub id: usize,
+    pub id: Id,
     pub result: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct BalanceResult {
-    pub confirmed: i64,
-    pub unconfirmed: i64,
+    #[serde(with = "amount::serde::as_sat")]
+    pub confirmed: SignedAmount,
+    // NOTE: can be negative if mempool txs spend confirmed coins
+    #[serde(with = "amount::serde::as_sat")]
+    pub unconfirmed: SignedAmount,
+}
+
+impl BalanceResult {
+    pub fn total(&self) -> SignedAmount {
+        self.confirmed + self.unconfirmed
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SHGetBalanceResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub balance: BalanceResult,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Confirmation status of a transaction as encoded in the `height` field.
+/// Ordered as in a script hash history: confirmed txs by height, then
+/// mempool txs, then mempool txs w/ unconfirmed parents.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "i64", into = "i64")]
+pub enum TxHeight {
+    Confirmed(usize),
+    Mempool,
+    UnconfirmedParents,
+}
+
+impl TxHeight {
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, Self::Confirmed(_))
+    }
+
+    pub fn confirmed_height(&self) -> Option<usize> {
+        match self {
+            Self::Confirmed(h) => Some(*h),
+            Self::Mempool | Self::UnconfirmedParents => None,
+        }
+    }
+}
+
+impl TryFrom<i64> for TxHeight {
+    type Error = String;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Mempool),
+            -1 => Ok(Self::UnconfirmedParents),
+            h if h > 0 => Ok(Self::Confirmed(h as usize)),
+            h => Err(format!("invalid height: {}", h)),
+        }
+    }
+}
+
+impl From<TxHeight> for i64 {
+    fn from(value: TxHeight) -> Self {
+        match value {
+            TxHeight::Confirmed(h) => h as i64,
+            TxHeight::Mempool => 0,
+            TxHeight::UnconfirmedParents => -1,
+        }
+    }
+}
+
+impl Display for TxHeight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", i64::from(*self))
+    }
+}
+
+impl PartialOrd for TxHeight {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TxHeight {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |h: &Self| match h {
+            Self::Confirmed(h) => (0, *h),
+            Self::Mempool => (1, 0),
+            Self::UnconfirmedParents => (2, 0),
+        };
+        key(self).cmp(&key(other))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct HistoryResult {
-    pub height: usize,
+    pub height: TxHeight,
     #[serde(rename = "tx_hash")]
     pub txid: Txid,
-    pub fee: Option<usize>,
+    // NOTE: only for mempool txs
+    #[serde(
+        default,
+        with = "amount::serde::as_sat::opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fee: Option<Amount>,
+}
+
+impl HistoryResult {
+    pub fn is_confirmed(&self) -> bool {
+        self.height.is_confirmed()
+    }
+}
+
+/// Order a history as expected for computing its status, see [`TxHeight`].
+/// The relative order of txs w/ the same height is kept.
+pub fn sort_history(history: &mut [HistoryResult]) {
+    history.sort_by_key(|tx| tx.height);
+}
+
+/// Parse a status, `null` means the script hash has no history.
+fn parse_status(status: &Option<String>) -> Result<Option<StatusHash>, Error> {
+    status
+        .as_ref()
+        .map(|s| StatusHash::from_str(s).map_err(|_| Error::InvalidStatus(s.clone())))
+        .transpose()
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SHGetHistoryResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub history: Vec<HistoryResult>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SHGetMempoolResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub mempool: Vec<HistoryResult>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct UtxoResult {
-    pub height: usize,
+    pub height: TxHeight,
     #[serde(rename = "tx_hash")]
     pub txid: Txid,
     #[serde(rename = "tx_pos")]
-    pub vout: usize,
-    pub value: usize,
+    pub vout: u32,
+    #[serde(with = "amount::serde::as_sat")]
+    pub value: Amount,
+}
+
+impl UtxoResult {
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(self.txid, self.vout)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SHListUnspentResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub unspent: Vec<UtxoResult>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-pub struct VerboseTx {
-    pub blockhash: String,
-    pub blocktime: usize,
-    pub confirmations: usize,
-    pub locktime: usize,
-    pub size: usize,
-    pub time: usize,
-    pub version: usize,
-    pub txid: String,
-    #[serde(rename = "hex")]
-    pub raw_tx: String,
-    // TODO: better parsing of vin/vout
-    pub vin: Value,
-    pub vout: Value,
+pub struct VerboseScriptSig {
+    pub asm: String,
+    pub hex: ScriptBuf,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-#[serde(untagged)]
-pub enum TxGetResult {
-    Raw(String),
-    Verbose(VerboseTx),
+pub struct VerboseTxIn {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    #[serde(rename = "scriptSig", skip_serializing_if = "Option::is_none")]
+    pub script_sig: Option<VerboseScriptSig>,
+    #[serde(rename = "txinwitness", default, skip_serializing_if = "Vec::is_empty")]
+    pub witness: Vec<String>,
+    pub sequence: u32,
 }
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-pub struct TxGetResponse {
-    pub id: usize,
-    pub result: TxGetResult,
+
+impl VerboseTxIn {
+    pub fn is_coinbase(&self) -> bool {
+        self.coinbase.is_some()
+    }
+
+    /// Returns the spent outpoint, `None` for a coinbase input.
+    pub fn previous_output(&self) -> Option<OutPoint> {
+        match (self.txid, self.vout) {
+            (Some(txid), Some(vout)) => Some(OutPoint { txid, vout }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ScriptType {
+    #[serde(rename = "pubkey")]
+    P2pk,
+    #[serde(rename = "pubkeyhash")]
+    P2pkh,
+    #[serde(rename = "scripthash")]
+    P2sh,
+    #[serde(rename = "multisig")]
+    Multisig,
+    #[serde(rename = "nulldata")]
+    NullData,
+    #[serde(rename = "witness_v0_keyhash")]
+    P2wpkh,
+    #[serde(rename = "witness_v0_scripthash")]
+    P2wsh,
+    #[serde(rename = "witness_v1_taproot")]
+    P2tr,
+    #[serde(rename = "anchor")]
+    Anchor,
+    #[serde(rename = "witness_unknown")]
+    WitnessUnknown,
+    #[serde(rename = "nonstandard", other)]
+    NonStandard,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct VerboseScriptPubKey {
+    pub asm: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    pub hex: ScriptBuf,
+    #[serde(rename = "type")]
+    pub script_type: ScriptType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address<NetworkUnchecked>>,
+}
+
+impl VerboseScriptPubKey {
+    /// The address of the script for `network`, the address returned by the
+    /// server if any must match it.
+    pub fn checked_address(&self, network: Network) -> Result<Option<Address>, Error> {
+        let expected = Address::from_script(&self.hex, network).ok();
+        match &self.address {
+            None => Ok(expected),
+            Some(address) => match &expected {
+                Some(a) if address.is_valid_for_network(network) && a.as_unchecked() == address => {
+                    Ok(expected)
+                }
+                _ => Err(Error::InvalidAddress(
+                    address.clone().assume_checked().to_string(),
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct VerboseTxOut {
+    #[serde(with = "amount::serde::as_btc")]
+    pub value: Amount,
+    pub n: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: VerboseScriptPubKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct VerboseTx {
+    // NOTE: block related fields are missing for mempool transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blockhash: Option<BlockHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocktime: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirmations: Option<u32>,
+    pub locktime: u32,
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vsize: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<u64>,
+    pub version: i32,
+    pub txid: Txid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Wtxid>,
+    #[serde(rename = "hex")]
+    pub raw_tx: String,
+    pub vin: Vec<VerboseTxIn>,
+    pub vout: Vec<VerboseTxOut>,
+}
+
+impl VerboseTx {
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmations.unwrap_or(0) > 0
+    }
+
+    /// Check the addresses returned for the outputs are valid for `network`
+    /// & match their script.
+    pub fn check_addresses(&self, network: Network) -> Result<(), Error> {
+        for out in &self.vout {
+            out.script_pubkey.checked_address(network)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum TxGetResult {
+    Raw(String),
+    Verbose(Box<VerboseTx>),
+}
+
+impl TxGetResult {
+    pub fn raw_tx(&self) -> &str {
+        match self {
+            TxGetResult::Raw(raw) => raw,
+            TxGetResult::Verbose(tx) => &tx.raw_tx,
+        }
+    }
+
+    /// Decode the raw transaction.
+    pub fn transaction(&self) -> Result<Transaction, Error> {
+        let raw = self.raw_tx();
+        let bytes = Vec::<u8>::from_hex(raw).map_err(|_| Error::InvalidTransaction(raw.into()))?;
+        deserialize(&bytes).map_err(|_| Error::InvalidTransaction(raw.into()))
+    }
+
+    /// Visit the raw transaction w/ `decoder` w/o building a `Transaction`,
+    /// returns its txid.
+    pub fn visit<V: Visitor>(
+        &self,
+        decoder: &mut SliceDecoder,
+        visitor: &mut V,
+    ) -> Result<Txid, Error> {
+        decoder.visit_tx(self.raw_tx(), visitor)
+    }
+
+    /// Decode the raw transaction and check its txid is the `expected` one, for
+    /// verbose results the `txid` field returned by the server is also checked.
+    pub fn checked_transaction(&self, expected: &Txid) -> Result<Transaction, Error> {
+        let tx = self.transaction()?;
+        let txid = tx.compute_txid();
+        if txid != *expected {
+            return Err(Error::TxidMismatch {
+                expected: *expected,
+                got: txid,
+            });
+        }
+        if let TxGetResult::Verbose(verbose) = self {
+            if verbose.txid != *expected {
+                return Err(Error::TxidMismatch {
+                    expected: *expected,
+                    got: verbose.txid,
+                });
+            }
+        }
+        Ok(tx)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TxGetResponse {
+    pub id: Id,
+    pub result: TxGetResult,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct GetMerkleResult {
-    merkle: Vec<String>,
+    merkle: Vec<TxMerkleNode>,
     block_height: usize,
     #[serde(rename = "pos")]
     tx_pos: usize,
 }
 
+impl GetMerkleResult {
+    pub fn new(branch: Vec<TxMerkleNode>, block_height: usize, tx_pos: usize) -> Self {
+        Self {
+            merkle: branch,
+            block_height,
+            tx_pos,
+        }
+    }
+
+    pub fn branch(&self) -> &[TxMerkleNode] {
+        &self.merkle
+    }
+
+    pub fn block_height(&self) -> usize {
+        self.block_height
+    }
+
+    pub fn position(&self) -> usize {
+        self.tx_pos
+    }
+
+    /// Verify that `txid` is included in the block of `header`, `header` must
+    /// be the header at [`GetMerkleResult::block_height()`].
+    pub fn verify(&self, txid: &Txid, header: &BlockHeader) -> Result<(), Error> {
+        verify_tx_inclusion(txid, &self.merkle, self.tx_pos, header)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct TxGetMerkleResponse {
-    pub id: usize,
+    pub id: Id,
     pub result: GetMerkleResult,
 }
 
@@ -438,46 +1466,288 @@ pub enum TxfromPosResult {
     WithMerkle {
         #[serde(rename = "tx_hash")]
         txid: Txid,
-        merkle: Vec<String>,
+        merkle: Vec<TxMerkleNode>,
     },
 }
 
+impl TxfromPosResult {
+    pub fn txid(&self) -> Txid {
+        match self {
+            Self::Simple(txid) | Self::WithMerkle { txid, .. } => *txid,
+        }
+    }
+
+    /// The merkle branch of the transaction, `None` if it was not requested.
+    pub fn branch(&self) -> Option<&[TxMerkleNode]> {
+        match self {
+            Self::Simple(_) => None,
+            Self::WithMerkle { merkle, .. } => Some(merkle),
+        }
+    }
+
+    /// Verify that the transaction is at `tx_pos` in the block of `header`.
+    pub fn verify(&self, tx_pos: usize, header: &BlockHeader) -> Result<(), Error> {
+        let branch = self.branch().ok_or(Error::MissingProof)?;
+        verify_tx_inclusion(&self.txid(), branch, tx_pos, header)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct TxFromPositionResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub tx: TxfromPosResult,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-pub struct Peer(
-    (
-        String,      /* ip address */
-        String,      /* domain */
-        Vec<String>, /* features */
-    ),
+/// A port advertised by a peer, w/o explicit port the peer listens on the
+/// default port of its network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerPort {
+    Default,
+    Port(u16),
+}
+
+type RawPeer = (
+    String,      /* ip address */
+    String,      /* hostname */
+    Vec<String>, /* features */
 );
 
+/// https://electrumx-spesmilo.readthedocs.io/en/latest/protocol-methods.html#server-peers-subscribe
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "RawPeer", into = "RawPeer")]
+pub struct Peer {
+    pub ip: String,
+    pub hostname: String,
+    pub version: Option<ProtocolVersion>,
+    pub pruning: Option<usize>,
+    pub tcp: Option<PeerPort>,
+    pub ssl: Option<PeerPort>,
+}
+
+impl Peer {
+    /// The TCP port of the peer, if it serves over TCP.
+    pub fn tcp_port(&self, network: Network) -> Option<u16> {
+        self.tcp.map(|port| match port {
+            PeerPort::Default => network::default_tcp_port(network),
+            PeerPort::Port(port) => port,
+        })
+    }
+
+    /// The SSL port of the peer, if it serves over SSL.
+    pub fn ssl_port(&self, network: Network) -> Option<u16> {
+        self.ssl.map(|port| match port {
+            PeerPort::Default => network::default_ssl_port(network),
+            PeerPort::Port(port) => port,
+        })
+    }
+}
+
+impl From<RawPeer> for Peer {
+    fn from(value: RawPeer) -> Self {
+        let (ip, hostname, features) = value;
+        let mut peer = Peer {
+            ip,
+            hostname,
+            version: None,
+            pruning: None,
+            tcp: None,
+            ssl: None,
+        };
+        let port = |value: &str| -> Option<PeerPort> {
+            if value.is_empty() {
+                Some(PeerPort::Default)
+            } else {
+                value.parse().ok().map(PeerPort::Port)
+            }
+        };
+        for feature in features {
+            // NOTE: unknown & malformed features are ignored, a single
+            // peer must not fail the whole list
+            let (kind, value) = match feature.char_indices().nth(1) {
+                Some((i, _)) => feature.split_at(i),
+                None => (feature.as_str(), ""),
+            };
+            match kind {
+                "v" => peer.version = ProtocolVersion::from_str(value).ok().or(peer.version),
+                "p" => peer.pruning = value.parse().ok().or(peer.pruning),
+                "t" => peer.tcp = port(value).or(peer.tcp),
+                "s" => peer.ssl = port(value).or(peer.ssl),
+                _ => {}
+            }
+        }
+        peer
+    }
+}
+
+impl From<Peer> for RawPeer {
+    fn from(value: Peer) -> Self {
+        let mut features = Vec::new();
+        if let Some(version) = value.version {
+            features.push(format!("v{}", version));
+        }
+        if let Some(pruning) = value.pruning {
+            features.push(format!("p{}", pruning));
+        }
+        let port = |kind: &str, port: PeerPort| match port {
+            PeerPort::Default => kind.to_string(),
+            PeerPort::Port(port) => format!("{}{}", kind, port),
+        };
+        if let Some(p) = value.tcp {
+            features.push(port("t", p));
+        }
+        if let Some(p) = value.ssl {
+            features.push(port("s", p));
+        }
+        (value.ip, value.hostname, features)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct ListPeersResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub peers: Vec<Peer>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
-pub struct ResultVersion((String, VersionKind));
+pub struct AddPeerResponse {
+    pub id: Id,
+    pub result: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct OutpointStatus {
+    // NOTE: `height` is missing if the outpoint does not exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<TxHeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spender_txhash: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spender_height: Option<TxHeight>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct OutpointSubscribeResponse {
+    pub id: Id,
+    #[serde(rename = "result")]
+    pub status: OutpointStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct OutpointUnsubscribeResponse {
+    pub id: Id,
+    pub result: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct OutpointNotification {
+    pub method: Method,
+    #[serde(rename = "params")]
+    pub status: ((Txid, u32), OutpointStatus),
+}
+
+impl OutpointNotification {
+    pub fn outpoint(&self) -> OutPoint {
+        let (txid, vout) = self.status.0;
+        OutPoint { txid, vout }
+    }
+}
+
+impl FromStr for OutpointNotification {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Error> {
+        let notif: Self =
+            serde_json::from_str(value).map_err(|_| Error::ResponseParsing(value.into()))?;
+        if let Method::OutpointSubscribe = notif.method {
+            Ok(notif)
+        } else {
+            Err(Error::WrongMethod)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MempoolInfo {
+    // NOTE: fees are in BTC/kvB
+    pub mempoolminfee: f64,
+    pub minrelaytxfee: f64,
+    pub incrementalrelayfee: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MempoolInfoResponse {
+    pub id: Id,
+    #[serde(rename = "result")]
+    pub info: MempoolInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PackageError {
+    pub txid: Txid,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct BroadcastPackageResult {
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<PackageError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TxBroadcastPackageResponse {
+    pub id: Id,
+    pub result: BroadcastPackageResult,
+}
+
+/// The response to a [`Method::Custom`] request, the result is kept as is
+/// until parsed w/ [`CustomResponse::result()`].
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CustomResponse {
+    pub id: Id,
+    #[serde(default)]
+    pub result: Value,
+    /// The method of the request.
+    #[serde(skip)]
+    pub method: String,
+}
+
+impl CustomResponse {
+    /// Parse the result as the result of `M`, fails w/ [`Error::WrongMethod`]
+    /// if the request was not a `M` request.
+    pub fn result<M: CustomMethod>(&self) -> Result<M::Result, Error> {
+        if self.method != M::NAME {
+            return Err(Error::WrongMethod);
+        }
+        M::Result::deserialize(&self.result)
+            .map_err(|e| Error::ResponseParsing(format!("{}: {}", M::NAME, e)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ResultVersion(pub (String, VersionKind));
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct VersionResponse {
-    pub id: usize,
+    pub id: Id,
     #[serde(rename = "result")]
     pub version: ResultVersion,
 }
 
+impl VersionResponse {
+    pub fn server_name(&self) -> &str {
+        &self.version.0 .0
+    }
+
+    pub fn protocol(&self) -> &VersionKind {
+        &self.version.0 .1
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use miniscript::bitcoin::{OutPoint, Script};
+    use miniscript::bitcoin::Script;
 
     use super::*;
 
@@ -486,7 +1756,10 @@ mod tests {
         let response = r#"{"id":3,"jsonrpc":"2.0","result":{"height":119367,"hex":"00000020835fdbdeeadd23463fad98b4e21aaa8519afde89eecd0eb224001317421cbb5f5e636df02303e51280b586bc596ee9326bc849bbb5993e121a8cab7e6b60e8ab593fe166ffff7f2000000000"}}"#;
 
         let parsed: HeaderNotification = serde_json::from_str(response).unwrap();
-        let expected = HeaderNotification::Single(SingleHeaderNotif { id: 3, header: Header { height: 119367, raw_header: "00000020835fdbdeeadd23463fad98b4e21aaa8519afde89eecd0eb224001317421cbb5f5e636df02303e51280b586bc596ee9326bc849bbb5993e121a8cab7e6b60e8ab593fe166ffff7f2000000000".into() }});
+        if let HeaderNotification::Single(SingleHeaderNotif { header, .. }) = &parsed {
+            assert_eq!(header.header().unwrap().time, 1726037849);
+        }
+        let expected = HeaderNotification::Single(SingleHeaderNotif { id: Id::Number(3), header: Header { height: 119367, raw_header: "00000020835fdbdeeadd23463fad98b4e21aaa8519afde89eecd0eb224001317421cbb5f5e636df02303e51280b586bc596ee9326bc849bbb5993e121a8cab7e6b60e8ab593fe166ffff7f2000000000".into() }});
         assert_eq!(parsed, expected)
     }
 
@@ -495,10 +1768,17 @@ mod tests {
         let response = r#"{"id":0,"jsonrpc":"2.0","result":"000000206e59d4b0d8d5b9daa4d3ad3093975b0f2a18a6909533350cbfb4b7a04adc6f5f380884ecf7425e488e7f2b249de516e839a5b2d48bcc9b65d45387ce5081c1e8563fe166ffff7f2001000000"}"#;
 
         let parsed: HeaderResponse = serde_json::from_str(response).unwrap();
+        let header = parsed.header().unwrap();
+        assert_eq!(header.time, 1726037846);
+        assert_eq!(
+            header.block_hash(),
+            BlockHash::from_str("79a359a82e68c185652f32fc045d7c438f803a8274c3753d3b7ea784b1efa9e4")
+                .unwrap()
+        );
         assert_eq!(
             parsed,
             HeaderResponse {
-                id: 0,
+                id: Id::Number(0),
                 raw_header: "000000206e59d4b0d8d5b9daa4d3ad3093975b0f2a18a6909533350cbfb4b7a04adc6f5f380884ecf7425e488e7f2b249de516e839a5b2d48bcc9b65d45387ce5081c1e8563fe166ffff7f2001000000".into()
             }
         )
@@ -509,17 +1789,40 @@ mod tests {
         let response = r#"{"id":0,"jsonrpc":"2.0","result":{"count":5,"hex":"000000206e59d4b0d8d5b9daa4d3ad3093975b0f2a18a6909533350cbfb4b7a04adc6f5f380884ecf7425e488e7f2b249de516e839a5b2d48bcc9b65d45387ce5081c1e8563fe166ffff7f200100000000000020e4a9efb184a77e3b3d75c374823a808f437c5d04fc322f6585c1682ea859a379874002727ca2397cbf8b45bffbd0463c1a8e4f52c23af48b3d8e30c0c4556bd1563fe166ffff7f200100000000000020d02dd6842a2be3611748c75b423d0199f86599a7f565de283ee09ffe3527cf49d2e107eae3f796827fb71fc950ee32f5c45c58704cd0f6de8c5125dfe18d0005573fe166ffff7f20000000000000002007e28823c56f2b29644eaa8060f1e62e622733fbb796a429119963f6318e4d012833a1ec146ca836cbd22f3be596ee73f00134c1edafaeb1178623cf480e554c573fe166ffff7f200600000000000020a7cc866c5522c258d4d08cf78aaf6dec40df9cba90c51b4fb63577dab6000b4805c639b49ecb0ddb0d6e922047310faefc6d69316e137084386a24238d1152ba573fe166ffff7f2000000000","max":2016}}"#;
 
         let parsed: HeadersResponse = serde_json::from_str(response).unwrap();
+        let headers = parsed.headers.headers().unwrap();
+        assert_eq!(headers.len(), 5);
+        assert!(crate::electrum::verify::verify_header_chain(None, &headers).is_ok());
         assert_eq!(
             parsed,
             HeadersResponse {
-                id: 0,
+                id: Id::Number(0),
                 headers: Headers {
                     count: 5,
                     raw_headers: "000000206e59d4b0d8d5b9daa4d3ad3093975b0f2a18a6909533350cbfb4b7a04adc6f5f380884ecf7425e488e7f2b249de516e839a5b2d48bcc9b65d45387ce5081c1e8563fe166ffff7f200100000000000020e4a9efb184a77e3b3d75c374823a808f437c5d04fc322f6585c1682ea859a379874002727ca2397cbf8b45bffbd0463c1a8e4f52c23af48b3d8e30c0c4556bd1563fe166ffff7f200100000000000020d02dd6842a2be3611748c75b423d0199f86599a7f565de283ee09ffe3527cf49d2e107eae3f796827fb71fc950ee32f5c45c58704cd0f6de8c5125dfe18d0005573fe166ffff7f20000000000000002007e28823c56f2b29644eaa8060f1e62e622733fbb796a429119963f6318e4d012833a1ec146ca836cbd22f3be596ee73f00134c1edafaeb1178623cf480e554c573fe166ffff7f200600000000000020a7cc866c5522c258d4d08cf78aaf6dec40df9cba90c51b4fb63577dab6000b4805c639b49ecb0ddb0d6e922047310faefc6d69316e137084386a24238d1152ba573fe166ffff7f2000000000".into(),
                     max: 2016
                 }
             }
-        )
+        );
+
+        // non-ASCII char straddling a header boundary
+        let raw = &parsed.headers.raw_headers;
+        let headers = Headers {
+            count: 2,
+            raw_headers: format!("{}é{}", &raw[..159], &raw[160..319]),
+            max: 2016,
+        };
+        assert_eq!(headers.raw_headers.len(), 320);
+        assert!(matches!(headers.headers(), Err(Error::InvalidHeader(_))));
+        let headers = Headers {
+            count: 3,
+            ..headers
+        };
+        assert!(headers.headers().is_err());
+
+        // count overflowing the expected length
+        let response = r#"{"count":2305843009213693952,"hex":"","max":2016}"#;
+        let headers: Headers = serde_json::from_str(response).unwrap();
+        assert!(matches!(headers.headers(), Err(Error::InvalidHeader(_))));
     }
 
     #[test]
@@ -575,8 +1878,8 @@ mod tests {
         let mut index = HashMap::new();
         for i in 14..35usize {
             let mut r = req.clone();
-            r.id = i;
-            index.insert(i, r);
+            r.id = Id::Number(i);
+            index.insert(Id::Number(i), r);
         }
 
         let response = r#"[{"id":14,"jsonrpc":"2.0","result":"1c8606707de065bef7474d719b76fb41cdff0090fffb78ca6b640c66ba9a9542"},{"id":15,"jsonrpc":"2.0","result":null},{"id":16,"jsonrpc":"2.0","result":null},{"id":17,"jsonrpc":"2.0","result":null},{"id":18,"jsonrpc":"2.0","result":null},{"id":19,"jsonrpc":"2.0","result":null},{"id":20,"jsonrpc":"2.0","result":null},{"id":21,"jsonrpc":"2.0","result":null},{"id":22,"jsonrpc":"2.0","result":null},{"id":23,"jsonrpc":"2.0","result":null},{"id":24,"jsonrpc":"2.0","result":null},{"id":25,"jsonrpc":"2.0","result":null},{"id":26,"jsonrpc":"2.0","result":null},{"id":27,"jsonrpc":"2.0","result":null},{"id":28,"jsonrpc":"2.0","result":null},{"id":29,"jsonrpc":"2.0","result":null},{"id":30,"jsonrpc":"2.0","result":null},{"id":31,"jsonrpc":"2.0","result":null},{"id":32,"jsonrpc":"2.0","result":null},{"id":33,"jsonrpc":"2.0","result":null},{"id":34,"jsonrpc":"2.0","result":null}]"#;
@@ -597,12 +1900,325 @@ mod tests {
         let response = r#"{"error":{"code":1,"message":"unsupported request Single(\"0.4\") by smart"},"id":0,"jsonrpc":"2.0"}"#;
 
         let response: ErrorResponse = serde_json::from_str(response).unwrap();
-        assert_eq!(response.error.code, 1);
-        assert_eq!(response.id, 0);
+        assert_eq!(response.error.code, RpcErrorCode::BadRequest);
+        assert_eq!(response.id, Some(Id::Number(0)));
         assert_eq!(
             response.error.message,
             r#"unsupported request Single("0.4") by smart"#
         );
+
+        // negative codes, `data` & null id
+        let response = r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"parse error","data":{"line":1}}}"#;
+        let response: ErrorResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.id, None);
+        assert_eq!(response.error.code, RpcErrorCode::ParseError);
+        assert_eq!(response.error.data, Some(serde_json::json!({"line": 1})));
+
+        // bitcoind error forwarded by electrs
+        let response = r#"{"jsonrpc":"2.0","id":2,"error":{"code":-5,"message":"No such mempool or blockchain transaction"}}"#;
+        let response: ErrorResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.error.code, RpcErrorCode::Other(-5));
+        assert_eq!(i64::from(response.error.code), -5);
+        assert_eq!(
+            serde_json::to_string(&response.error).unwrap(),
+            r#"{"code":-5,"message":"No such mempool or blockchain transaction"}"#
+        );
+    }
+
+    #[test]
+    fn parse_dispatch() {
+        let mut index = HashMap::new();
+        index.insert(Id::Number(4), Request::banner().id(4));
+
+        // `error: null` is not an error
+        let raw = r#"{"jsonrpc":"2.0","id":4,"result":"hello","error":null}"#;
+        assert!(matches!(
+            Response::try_parse(raw, &index).unwrap(),
+            Response::Banner(_)
+        ));
+
+        // notification of a method w/o subscription
+        let raw = r#"{"jsonrpc":"2.0","method":"server.banner","params":[]}"#;
+        assert!(matches!(
+            Response::try_parse(raw, &index),
+            Err(Error::WrongMethod)
+        ));
+
+        let raw = r#"{"jsonrpc":"2.0","id":5,"result":"hello"}"#;
+        assert!(matches!(
+            Response::try_parse(raw, &index),
+            Err(Error::ResponseId(Id::Number(5)))
+        ));
+
+        let raw = r#"{"jsonrpc":"2.0","id":4,"result":12}"#;
+        assert!(matches!(
+            Response::try_parse(raw, &index),
+            Err(Error::ResponseParsing(_))
+        ));
+
+        assert!(matches!(
+            Response::try_parse("[1, 2", &index),
+            Err(Error::RawResponseParsing(_))
+        ));
+        // only the malformed element fails
+        assert!(matches!(
+            parse_str_response("[1]", &index).unwrap()[..],
+            [Err(Error::RawResponseParsing(_))]
+        ));
+        assert!(matches!(
+            parse_str_response("[1", &index),
+            Err(Error::BatchParsing)
+        ));
+    }
+
+    #[test]
+    fn unknown_messages() {
+        let mut index = HashMap::new();
+        index.insert(Id::Number(4), Request::banner().id(4));
+
+        // no `jsonrpc` field
+        let raw = r#"{"id":4,"result":"hello"}"#;
+        assert!(matches!(
+            Response::try_parse(raw, &index).unwrap(),
+            Response::Banner(_)
+        ));
+
+        let peers = r#"{"jsonrpc":"2.0","method":"server.peers.subscribe","params":[[]]}"#;
+        let vendor = r#"{"jsonrpc":"2.0","method":"vendor.ext","params":{"a":1}}"#;
+        let unsolicited = r#"{"jsonrpc":"2.0","id":9,"result":"hey"}"#;
+        let no_id = r#"{"jsonrpc":"2.0","result":"hey"}"#;
+        for raw in [peers, vendor, unsolicited, no_id] {
+            assert!(Response::try_parse(raw, &index).is_err());
+            assert!(Response::try_parse_with(raw, &index, Strictness::Skip)
+                .unwrap()
+                .is_none());
+        }
+
+        let unknown = |raw| match Response::try_parse_with(raw, &index, Strictness::Lenient) {
+            Ok(Some(Response::Unknown(m))) => m,
+            r => panic!("unexpected response {:?}", r),
+        };
+        let m = unknown(peers);
+        assert_eq!(m.method.as_deref(), Some("server.peers.subscribe"));
+        assert_eq!(m.id, None);
+        let m = unknown(vendor);
+        assert_eq!(m.method.as_deref(), Some("vendor.ext"));
+        assert_eq!(m.value["params"]["a"], 1);
+        let m = unknown(unsolicited);
+        assert_eq!((m.method, m.id), (None, Some(Id::Number(9))));
+        assert_eq!(
+            serde_json::to_value(Response::Unknown(unknown(no_id))).unwrap(),
+            serde_json::from_str::<Value>(no_id).unwrap()
+        );
+
+        // a malformed result is still an error
+        let raw = r#"{"jsonrpc":"2.0","id":4,"result":12}"#;
+        assert!(Response::try_parse_with(raw, &index, Strictness::Lenient).is_err());
+
+        let raw = format!(r#"[{},{}]"#, r#"{"id":4,"result":"hello"}"#, unsolicited);
+        assert!(matches!(
+            parse_str_response(&raw, &index).unwrap()[..],
+            [
+                Ok(Response::Banner(_)),
+                Err(Error::ResponseId(Id::Number(9)))
+            ]
+        ));
+        assert_eq!(
+            parse_str_response_with(&raw, &index, Strictness::Skip)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            parse_str_response_with(&raw, &index, Strictness::Lenient).unwrap()[..],
+            [Ok(Response::Banner(_)), Ok(Response::Unknown(_))]
+        ));
+    }
+
+    #[test]
+    fn string_ids() {
+        let mut index = HashMap::new();
+        index.insert(Id::from("req-1"), Request::banner().id("req-1"));
+        index.insert(Id::Number(1), Request::ping().id(1));
+
+        let raw = r#"{"jsonrpc":"2.0","id":"req-1","result":"hello"}"#;
+        match Response::try_parse(raw, &index).unwrap() {
+            Response::Banner(r) => assert_eq!(r.id, Id::from("req-1")),
+            r => panic!("unexpected response {:?}", r),
+        }
+        // "1" is not 1
+        let raw = r#"{"jsonrpc":"2.0","id":"1","result":null}"#;
+        assert!(matches!(
+            Response::try_parse(raw, &index),
+            Err(Error::ResponseId(Id::String(_)))
+        ));
+
+        let raw = r#"[{"jsonrpc":"2.0","id":1,"result":null},{"jsonrpc":"2.0","id":"req-1","error":{"code":1,"message":"no"}}]"#;
+        let batch: Vec<_> = parse_str_response(raw, &index)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert!(matches!(batch[0], Response::Ping(_)));
+        match &batch[1] {
+            Response::Error(e) => assert_eq!(e.method, Some(Method::Banner)),
+            r => panic!("unexpected response {:?}", r),
+        }
+
+        let request = Request::banner().id("req-1");
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","id":"req-1","method":"server.banner","params":[]}"#
+        );
+    }
+
+    #[test]
+    fn custom_method() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Fee {
+            fee: u64,
+        }
+        struct GetFee;
+        impl CustomMethod for GetFee {
+            const NAME: &'static str = "server.get_fee";
+            type Params = (String,);
+            type Result = Fee;
+        }
+
+        let request = Request::custom::<GetFee>(&("abc".into(),)).unwrap().id(1);
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"server.get_fee","params":["abc"]}"#
+        );
+        let request = Request::custom_raw("server.toto", Value::Null).id(2);
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","id":2,"method":"server.toto","params":[]}"#
+        );
+
+        let mut index = HashMap::new();
+        index.insert(Id::Number(0), Request::banner().id(0));
+        index.insert(
+            Id::Number(1),
+            Request::custom::<GetFee>(&("abc".into(),)).unwrap().id(1),
+        );
+        index.insert(Id::Number(2), request);
+
+        let raw = r#"[
+            {"jsonrpc":"2.0","id":0,"result":"hello"},
+            {"jsonrpc":"2.0","id":1,"result":{"fee":12}},
+            {"jsonrpc":"2.0","id":2,"result":{"fee":"12"}}
+        ]"#;
+        let batch: Vec<_> = parse_str_response(raw, &index)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert!(matches!(batch[0], Response::Banner(_)));
+        match &batch[1] {
+            Response::Custom(r) => {
+                assert_eq!(r.method, "server.get_fee");
+                assert_eq!(r.result::<GetFee>().unwrap(), Fee { fee: 12 });
+            }
+            r => panic!("unexpected response {:?}", r),
+        }
+        match &batch[2] {
+            Response::Custom(r) => {
+                assert_eq!(r.result, serde_json::json!({"fee": "12"}));
+                assert!(matches!(r.result::<GetFee>(), Err(Error::WrongMethod)));
+            }
+            r => panic!("unexpected response {:?}", r),
+        }
+
+        // the result does not match the expected type
+        let raw = r#"{"jsonrpc":"2.0","id":1,"result":{"fee":"12"}}"#;
+        match Response::try_parse(raw, &index).unwrap() {
+            Response::Custom(r) => assert!(matches!(
+                r.result::<GetFee>(),
+                Err(Error::ResponseParsing(_))
+            )),
+            r => panic!("unexpected response {:?}", r),
+        }
+
+        let raw = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"unknown method"}}"#;
+        match Response::try_parse(raw, &index).unwrap() {
+            Response::Error(e) => {
+                assert_eq!(e.method, Some(Method::Custom("server.get_fee".into())))
+            }
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+
+    #[test]
+    fn batch_responses() {
+        let mut index = HashMap::new();
+        for id in 0..5 {
+            index.insert(Id::Number(id), Request::banner().id(id));
+        }
+        let raw = r#"[
+            {"jsonrpc":"2.0","id":0,"result":"hello"},
+            {"jsonrpc":"2.0","id":1,"result":12},
+            {"jsonrpc":"2.0","id":0,"result":"again"},
+            {"jsonrpc":"2.0","id":7,"result":"who?"},
+            {"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"invalid request"}},
+            {"jsonrpc":"2.0","id":3,"error":{"code":1,"message":"no banner"}}
+        ]"#;
+        let mut batch = BatchResponses::parse(raw, &index).unwrap();
+        assert_eq!(batch.duplicates, vec![Id::Number(0)]);
+        assert_eq!(batch.unknown.len(), 2);
+        assert!(matches!(
+            batch.unknown[0],
+            Err(Error::ResponseId(Id::Number(7)))
+        ));
+        assert!(matches!(&batch.unknown[1], Ok(Response::Error(e)) if e.id.is_none()));
+        let mut missing = batch.missing(&[0, 1, 2, 3, 4].map(Id::Number));
+        missing.sort();
+        assert_eq!(missing, vec![Id::Number(2), Id::Number(4)]);
+
+        match batch.take(&Id::Number(0)).unwrap() {
+            Response::Banner(r) => assert_eq!(r.result, "hello"),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert!(matches!(
+            batch.take(&Id::Number(0)),
+            Err(Error::MissingResponse(Id::Number(0)))
+        ));
+        assert!(matches!(
+            batch.take(&Id::Number(1)),
+            Err(Error::ResponseParsing(_))
+        ));
+        let results = batch.into_ordered(&[Id::Number(3), Id::Number(4)]);
+        assert!(matches!(&results[0], Ok(Response::Error(e)) if e.method == Some(Method::Banner)));
+        assert!(matches!(
+            results[1],
+            Err(Error::MissingResponse(Id::Number(4)))
+        ));
+
+        assert!(matches!(
+            BatchResponses::parse("{}", &index),
+            Err(Error::BatchParsing)
+        ));
+    }
+
+    #[test]
+    fn batch_error_method() {
+        let mut index = HashMap::new();
+        index.insert(Id::Number(0), Request::banner());
+        index.insert(Id::Number(1), Request::header(1_000_000).id(1));
+        let raw = r#"[{"jsonrpc":"2.0","id":0,"result":"hello"},{"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"invalid height"}}]"#;
+        let batch: Vec<_> = parse_str_response(raw, &index)
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        match &batch[1] {
+            Response::Error(e) => {
+                assert_eq!(e.id, Some(Id::Number(1)));
+                assert_eq!(e.method, Some(Method::BlockHeader));
+                assert_eq!(e.error.code, RpcErrorCode::InvalidParams);
+            }
+            r => panic!("unexpected response {:?}", r),
+        }
     }
 
     #[test]
@@ -653,8 +2269,19 @@ mod tests {
                 .unwrap()
         );
         assert_eq!(response.unspent[0].vout, 1);
-        assert_eq!(response.unspent[0].height, 861250);
-        assert_eq!(response.unspent[0].value, 566888);
+        assert_eq!(response.unspent[0].height, TxHeight::Confirmed(861250));
+        assert_eq!(response.unspent[0].value, Amount::from_sat(566888));
+        assert_eq!(
+            response.unspent[0].outpoint(),
+            OutPoint::from_str(
+                "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0:1"
+            )
+            .unwrap()
+        );
+
+        // output index out of the u32 range
+        let response = r#"{"jsonrpc": "2.0", "result": [{"tx_hash": "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0", "tx_pos": 4294967296, "height": 861250, "value": 566888}], "id": 0}"#;
+        assert!(serde_json::from_str::<SHListUnspentResponse>(response).is_err());
     }
 
     #[test]
@@ -663,8 +2290,9 @@ mod tests {
             r#"{"jsonrpc": "2.0", "result": {"confirmed": 566888, "unconfirmed": 0}, "id": 0}"#;
         let response: SHGetBalanceResponse = serde_json::from_str(response).unwrap();
         assert_eq!(response.id, 0);
-        assert_eq!(response.balance.confirmed, 566888);
-        assert_eq!(response.balance.unconfirmed, 0);
+        assert_eq!(response.balance.confirmed, SignedAmount::from_sat(566888));
+        assert_eq!(response.balance.unconfirmed, SignedAmount::ZERO);
+        assert_eq!(response.balance.total(), SignedAmount::from_sat(566888));
     }
 
     #[test]
@@ -678,7 +2306,93 @@ mod tests {
             Txid::from_str("b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0")
                 .unwrap()
         );
-        assert_eq!(response.history[0].height, 861250);
+        assert_eq!(response.history[0].height, TxHeight::Confirmed(861250));
+    }
+
+    #[test]
+    fn tx_height() {
+        let heights: Vec<TxHeight> = serde_json::from_str("[-1, 0, 1, 861250]").unwrap();
+        assert_eq!(
+            heights,
+            vec![
+                TxHeight::UnconfirmedParents,
+                TxHeight::Mempool,
+                TxHeight::Confirmed(1),
+                TxHeight::Confirmed(861250)
+            ]
+        );
+        assert_eq!(serde_json::to_string(&heights).unwrap(), "[-1,0,1,861250]");
+        assert!(serde_json::from_str::<TxHeight>("-2").is_err());
+        assert_eq!(TxHeight::Confirmed(3).confirmed_height(), Some(3));
+        assert_eq!(TxHeight::Mempool.confirmed_height(), None);
+        assert!(TxHeight::Confirmed(861250) < TxHeight::Mempool);
+        assert!(TxHeight::Mempool < TxHeight::UnconfirmedParents);
+
+        let response = r#"{"jsonrpc": "2.0", "result": [{"tx_hash": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "height": -1, "fee": 250}], "id": 0}"#;
+        let response: SHGetMempoolResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.mempool[0].height, TxHeight::UnconfirmedParents);
+        assert_eq!(response.mempool[0].fee, Some(Amount::from_sat(250)));
+
+        let response = r#"{"jsonrpc": "2.0", "result": [{"tx_hash": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "tx_pos": 0, "height": 0, "value": 1000}], "id": 0}"#;
+        let response: SHListUnspentResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.unspent[0].height, TxHeight::Mempool);
+    }
+
+    #[test]
+    fn status_hash() {
+        let response = r#"{"jsonrpc": "2.0", "result": [{"tx_hash": "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0", "height": 861250}], "id": 0}"#;
+        let response: SHGetHistoryResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(
+            StatusHash::from_history(&response.history).unwrap(),
+            StatusHash::from_str(
+                "ff59428750198a04004549c8e670067e9d217a001788cc62ff2b41565acadc48"
+            )
+            .unwrap()
+        );
+        assert!(StatusHash::from_history(&[]).is_none());
+
+        // w/ mempool txs, out of order
+        let response = r#"{"jsonrpc": "2.0", "result": [{"tx_hash": "e48b08df0afa01a7339335fb6b6964100d11985765cbc6afcde990fd65856a9b", "height": -1, "fee": 200}, {"tx_hash": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "height": 0, "fee": 100}, {"tx_hash": "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0", "height": 861250}], "id": 0}"#;
+        let mut response: SHGetHistoryResponse = serde_json::from_str(response).unwrap();
+        assert!(!response.history[0].is_confirmed());
+        assert!(response.history[2].is_confirmed());
+        sort_history(&mut response.history);
+        let heights: Vec<_> = response.history.iter().map(|tx| tx.height).collect();
+        assert_eq!(
+            heights,
+            vec![
+                TxHeight::Confirmed(861250),
+                TxHeight::Mempool,
+                TxHeight::UnconfirmedParents
+            ]
+        );
+        let status = StatusHash::from_history(&response.history).unwrap();
+        assert_eq!(
+            status.to_string(),
+            "1ec1fbed798b2082cbda8445ac808038ba9eb39ff5aa2952b7cf85f69d357b96"
+        );
+
+        // notification
+        let notif = format!(
+            r#"{{"jsonrpc":"2.0","method":"blockchain.scripthash.subscribe","params":["95ebd95e7c0763b785d12b1d20d9f548fa5bb809f120afb0dd11276fa1ce8352","{}"]}}"#,
+            status
+        );
+        let notif = SHNotification::from_str(&notif).unwrap();
+        assert_eq!(notif.status_hash().unwrap(), Some(status));
+        assert_eq!(
+            notif.script_hash().to_string(),
+            "95ebd95e7c0763b785d12b1d20d9f548fa5bb809f120afb0dd11276fa1ce8352"
+        );
+
+        let response = r#"{"id":1,"jsonrpc":"2.0","result":null}"#;
+        let response: SHSubscribeResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.status_hash().unwrap(), None);
+        let response = r#"{"id":1,"jsonrpc":"2.0","result":"some_garbage_string"}"#;
+        let response: SHSubscribeResponse = serde_json::from_str(response).unwrap();
+        assert!(matches!(
+            response.status_hash(),
+            Err(Error::InvalidStatus(_))
+        ));
     }
 
     #[test]
@@ -695,6 +2409,18 @@ mod tests {
             response.features.genesis,
             "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
         );
+        assert_eq!(
+            response.features.genesis_hash().unwrap(),
+            miniscript::bitcoin::constants::genesis_block(Network::Bitcoin).block_hash()
+        );
+        assert!(response.features.check_network(Network::Bitcoin).is_ok());
+        assert!(matches!(
+            response.features.check_network(Network::Testnet),
+            Err(Error::WrongNetwork {
+                expected: Network::Testnet,
+                ..
+            })
+        ));
         assert_eq!(response.features.hash_function, "sha256");
         assert!(response.features.services.is_some());
         assert!(response.features.services.unwrap().is_empty());
@@ -718,7 +2444,7 @@ mod tests {
         let response: FeaturesResponse = serde_json::from_str(response).unwrap();
 
         let expected = FeaturesResponse {
-            id: 0,
+            id: Id::Number(0),
             features: FeaturesResult {
                 genesis: "abc".into(),
                 hosts: Hosts::Single(Host {
@@ -743,6 +2469,22 @@ mod tests {
         let response: DonationResponse = serde_json::from_str(response).unwrap();
         assert_eq!(response.id, 0);
         assert_eq!(response.address, Some("make_me_rich".into()));
+        assert!(matches!(
+            response.address(Network::Bitcoin),
+            Some(Err(Error::InvalidAddress(_)))
+        ));
+
+        let response = r#"{"jsonrpc": "2.0", "result": "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq", "id": 0}"#;
+        let response: DonationResponse = serde_json::from_str(response).unwrap();
+        assert!(response.address(Network::Bitcoin).unwrap().is_ok());
+        assert!(matches!(
+            response.address(Network::Testnet),
+            Some(Err(Error::InvalidAddress(_)))
+        ));
+
+        let response = r#"{"jsonrpc": "2.0", "result": "", "id": 0}"#;
+        let response: DonationResponse = serde_json::from_str(response).unwrap();
+        assert!(response.address(Network::Bitcoin).is_none());
     }
 
     #[test]
@@ -752,6 +2494,16 @@ mod tests {
         let response: EstimateFeeResponse = serde_json::from_str(response).unwrap();
         assert_eq!(response.id, 0);
         assert_eq!(response.fee, OptionalFee::Fee(0.00003006));
+        // 3006 sat/kvB
+        assert_eq!(response.fee_rate(), Some(FeeRate::from_sat_per_kwu(752)));
+
+        let response = r#"{"jsonrpc": "2.0", "result": -1, "id": 0}"#;
+        let response: EstimateFeeResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.fee_rate(), None);
+
+        let rate = FeeRate::from_sat_per_kwu(752);
+        assert_eq!(OptionalFee::from(Some(rate)).fee_rate(), Some(rate));
+        assert_eq!(OptionalFee::from(None), OptionalFee::None(-1));
     }
 
     #[test]
@@ -761,13 +2513,40 @@ mod tests {
         let response: FeeHistogramResponse = serde_json::from_str(response).unwrap();
         assert_eq!(response.id, 0);
         assert_eq!(response.histogram.len(), 4);
-        assert_eq!(response.histogram[1].0, 3);
+        assert_eq!(response.histogram[1].0, 3.0);
         assert_eq!(response.histogram[2].1, 12058673);
         let expected = FeeHistogramResponse {
-            id: 0,
-            histogram: vec![(5, 103673), (3, 238053), (2, 12058673), (1, 34188435)],
+            id: Id::Number(0),
+            histogram: vec![
+                (5.0, 103673),
+                (3.0, 238053),
+                (2.0, 12058673),
+                (1.0, 34188435),
+            ],
         };
         assert_eq!(response, expected);
+        let rates = response.fee_rates();
+        assert_eq!(rates[1].0, FeeRate::from_sat_per_vb(3).unwrap());
+        assert_eq!(rates[1].1, Weight::from_vb(238053).unwrap());
+
+        // fractional fee rates
+        let response = r#"{"jsonrpc": "2.0", "result": [[1.5, 1000], [0.1, 2000]], "id": 0}"#;
+        let response: FeeHistogramResponse = serde_json::from_str(response).unwrap();
+        let rates = response.fee_rates();
+        assert_eq!(rates[0].0, FeeRate::from_sat_per_kwu(375));
+        assert_eq!(rates[1].0, FeeRate::from_sat_per_kwu(25));
+
+        // sizes out of the weight range
+        let response =
+            r#"{"jsonrpc": "2.0", "result": [[1.0, 18446744073709551615], [1.0, 10]], "id": 0}"#;
+        let response: FeeHistogramResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(
+            response.fee_rates(),
+            vec![(
+                FeeRate::from_sat_per_vb(1).unwrap(),
+                Weight::from_vb(10).unwrap()
+            )]
+        );
     }
 
     #[test]
@@ -777,6 +2556,10 @@ mod tests {
         let response: RelayFeeResponse = serde_json::from_str(response).unwrap();
         assert_eq!(response.id, 0);
         assert_eq!(response.fee, OptionalFee::Fee(0.00001));
+        assert_eq!(
+            response.fee_rate(),
+            Some(FeeRate::from_sat_per_vb(1).unwrap())
+        );
     }
 
     #[test]
@@ -786,18 +2569,45 @@ mod tests {
         let response: TxGetMerkleResponse = serde_json::from_str(response).unwrap();
         assert_eq!(response.id, 0);
         let expected = TxGetMerkleResponse {
-            id: 0,
+            id: Id::Number(0),
             result: GetMerkleResult {
                 merkle: vec![
-                    "ffa0267c8f2af736858894d6f3e5081a05e2ec16dc98f78a80f376ce35077491".into(),
-                    "d0039b6be844e631698f57fa02bbfbfb5e8b680f3ebb17646631e6ec9f91f6e6".into(),
-                    "bbe3063ce3d04c2e3f18e494a287867f81ad1182b62a1ecb3e1ea2686edcea20".into(),
-                    "1d15a2423f52d4aa281a2ac389c0a5a601ed08bdf814494ddf7697196860b801".into(),
-                    "b63e58ec9f5ee2e268f1540af8bb0e5b8fd0ce7cd6877a174e6178c676d6b574".into(),
-                    "7407724b98c77cdbf070f3fe297839de2bef50fead98b452883f0f3a4643cde2".into(),
-                    "d029f17725e71e3c025bd7d0505006dc859af5450d0b6dd092ee88c0d98f9a25".into(),
-                    "e4df974d81ab4fdf35f635024a01f20aa88af9f520215708b339dbc5bceddf63".into(),
-                    "20f4202f18666483306f175e1c9c521741845afcf2710f0b0d42602ac72c5fd6".into(),
+                    TxMerkleNode::from_str(
+                        "ffa0267c8f2af736858894d6f3e5081a05e2ec16dc98f78a80f376ce35077491",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "d0039b6be844e631698f57fa02bbfbfb5e8b680f3ebb17646631e6ec9f91f6e6",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "bbe3063ce3d04c2e3f18e494a287867f81ad1182b62a1ecb3e1ea2686edcea20",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "1d15a2423f52d4aa281a2ac389c0a5a601ed08bdf814494ddf7697196860b801",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "b63e58ec9f5ee2e268f1540af8bb0e5b8fd0ce7cd6877a174e6178c676d6b574",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "7407724b98c77cdbf070f3fe297839de2bef50fead98b452883f0f3a4643cde2",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "d029f17725e71e3c025bd7d0505006dc859af5450d0b6dd092ee88c0d98f9a25",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "e4df974d81ab4fdf35f635024a01f20aa88af9f520215708b339dbc5bceddf63",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "20f4202f18666483306f175e1c9c521741845afcf2710f0b0d42602ac72c5fd6",
+                    )
+                    .unwrap(),
                 ],
                 block_height: 200_000,
                 tx_pos: 2,
@@ -806,6 +2616,63 @@ mod tests {
         assert_eq!(expected, response);
     }
 
+    #[test]
+    fn tx_from_pos_verify() {
+        use crate::electrum::verify::tests::{block, txids};
+
+        let txids = txids(6);
+        let (header, branch) = block(&txids, 4);
+        let result = TxfromPosResult::WithMerkle {
+            txid: txids[4],
+            merkle: branch.clone(),
+        };
+        assert_eq!(result.txid(), txids[4]);
+        assert!(result.verify(4, &header).is_ok());
+        assert!(matches!(
+            result.verify(5, &header),
+            Err(Error::InvalidProof)
+        ));
+        // same leaf once the position is truncated to the depth of the branch
+        assert!(matches!(
+            result.verify(4 + 8, &header),
+            Err(Error::InvalidProof)
+        ));
+
+        let simple = TxfromPosResult::Simple(txids[4]);
+        assert!(simple.branch().is_none());
+        assert!(matches!(
+            simple.verify(4, &header),
+            Err(Error::MissingProof)
+        ));
+    }
+
+    #[test]
+    fn tx_get_merkle_verify() {
+        use crate::electrum::verify::tests::{block, txids};
+
+        let txids = txids(5);
+        let (header, branch) = block(&txids, 3);
+        let result = GetMerkleResult {
+            merkle: branch.clone(),
+            block_height: 10,
+            tx_pos: 3,
+        };
+        assert_eq!(result.block_height(), 10);
+        assert_eq!(result.position(), 3);
+        assert_eq!(result.branch(), &branch[..]);
+        assert!(result.verify(&txids[3], &header).is_ok());
+        assert!(matches!(
+            result.verify(&txids[2], &header),
+            Err(Error::InvalidProof)
+        ));
+
+        let (other, _) = block(&txids[..4], 3);
+        assert!(matches!(
+            result.verify(&txids[3], &other),
+            Err(Error::InvalidProof)
+        ));
+    }
+
     #[test]
     fn tx_from_pos() {
         let response = r#"{"jsonrpc": "2.0", "result": "ffa0267c8f2af736858894d6f3e5081a05e2ec16dc98f78a80f376ce35077491", "id": 0}"#;
@@ -817,7 +2684,7 @@ mod tests {
 
         let response: TxFromPositionResponse = serde_json::from_str(response).unwrap();
         let expected = TxFromPositionResponse {
-            id: 0,
+            id: Id::Number(0),
             tx: TxfromPosResult::Simple(outpoint.txid),
         };
         assert_eq!(response, expected);
@@ -831,21 +2698,583 @@ mod tests {
         .unwrap();
 
         let expected = TxFromPositionResponse {
-            id: 1,
+            id: Id::Number(1),
             tx: TxfromPosResult::WithMerkle {
                 txid: outpoint.txid,
                 merkle: vec![
-                    "e48b08df0afa01a7339335fb6b6964100d11985765cbc6afcde990fd65856a9b".into(),
-                    "12a6c68b6c033d6704bda3437370b3e7d65bec81b2e3f4eafb17632197f0b6c7".into(),
-                    "c0dbecba7c7990f3bfbe727dd9a7371225852600dc0a0f07e68b3ec7c4fd629e".into(),
-                    "8e351c5bac49e6dbf08bc67cc1f57fb4dbea0383336d0ee2c38fefc8736b18eb".into(),
-                    "aa7171ca4f639d14050101ac602f3f526abec753414b3b4648071b252434e38e".into(),
-                    "583b92abff3481905c686d3ff594c4a1d6a00bab25deb3397369b9e49adf11ae".into(),
-                    "6a4d797a4d3e162a951ccd142fe6ca86e12006145f1670c5d1aa5e7bfcc05fa3".into(),
-                    "c6dd553f393d1b7694ae168e8f5efeba8db4c3b000c2d9bf5205dd19f96c08a8".into(),
+                    TxMerkleNode::from_str(
+                        "e48b08df0afa01a7339335fb6b6964100d11985765cbc6afcde990fd65856a9b",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "12a6c68b6c033d6704bda3437370b3e7d65bec81b2e3f4eafb17632197f0b6c7",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "c0dbecba7c7990f3bfbe727dd9a7371225852600dc0a0f07e68b3ec7c4fd629e",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "8e351c5bac49e6dbf08bc67cc1f57fb4dbea0383336d0ee2c38fefc8736b18eb",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "aa7171ca4f639d14050101ac602f3f526abec753414b3b4648071b252434e38e",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "583b92abff3481905c686d3ff594c4a1d6a00bab25deb3397369b9e49adf11ae",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "6a4d797a4d3e162a951ccd142fe6ca86e12006145f1670c5d1aa5e7bfcc05fa3",
+                    )
+                    .unwrap(),
+                    TxMerkleNode::from_str(
+                        "c6dd553f393d1b7694ae168e8f5efeba8db4c3b000c2d9bf5205dd19f96c08a8",
+                    )
+                    .unwrap(),
                 ],
             },
         };
         assert_eq!(response, expected);
     }
+
+    #[test]
+    fn outpoint_subscribe() {
+        let response = r#"{"jsonrpc": "2.0", "result": {"height": 861250, "spender_txhash": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "spender_height": 0}, "id": 0}"#;
+
+        let response: OutpointSubscribeResponse = serde_json::from_str(response).unwrap();
+        let expected = OutpointSubscribeResponse {
+            id: Id::Number(0),
+            status: OutpointStatus {
+                height: Some(TxHeight::Confirmed(861250)),
+                spender_txhash: Some(
+                    Txid::from_str(
+                        "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681",
+                    )
+                    .unwrap(),
+                ),
+                spender_height: Some(TxHeight::Mempool),
+            },
+        };
+        assert_eq!(response, expected);
+
+        // funding tx in mempool w/ unconfirmed parents
+        let response = r#"{"jsonrpc": "2.0", "result": {"height": -1}, "id": 1}"#;
+        let response: OutpointSubscribeResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.id, 1);
+        assert_eq!(response.status.height, Some(TxHeight::UnconfirmedParents));
+        assert!(response.status.spender_txhash.is_none());
+
+        // unknown outpoint
+        let response = r#"{"jsonrpc": "2.0", "result": {}, "id": 2}"#;
+        let response: OutpointSubscribeResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.status, OutpointStatus::default());
+    }
+
+    #[test]
+    fn outpoint_unsubscribe() {
+        let response = r#"{"jsonrpc": "2.0", "result": true, "id": 3}"#;
+        let response: OutpointUnsubscribeResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.id, 3);
+        assert!(response.result);
+    }
+
+    #[test]
+    fn outpoint_notification() {
+        let response = r#"{"jsonrpc": "2.0", "method": "blockchain.outpoint.subscribe", "params": [["b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0", 1], {"height": 861250, "spender_txhash": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "spender_height": 861251}]}"#;
+
+        let notif = OutpointNotification::from_str(response).unwrap();
+        assert_eq!(notif.method, Method::OutpointSubscribe);
+        assert_eq!(
+            notif.outpoint(),
+            OutPoint::from_str(
+                "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0:1"
+            )
+            .unwrap()
+        );
+        assert_eq!(notif.status.1.height, Some(TxHeight::Confirmed(861250)));
+        assert_eq!(
+            notif.status.1.spender_height,
+            Some(TxHeight::Confirmed(861251))
+        );
+
+        let parsed = Response::try_parse(response, &HashMap::new()).unwrap();
+        assert_eq!(parsed, Response::OutpointNotification(notif));
+
+        let sh_notif = r#" {"jsonrpc":"2.0","method":"blockchain.scripthash.subscribe","params":["95ebd95e7c0763b785d12b1d20d9f548fa5bb809f120afb0dd11276fa1ce8352","9bf1d98ff899eafd048290199144aed63e3d7ccbc8925e8351a4c1e8af2137f4"]}"#;
+        assert!(matches!(
+            OutpointNotification::from_str(sh_notif),
+            Err(Error::ResponseParsing(_))
+        ));
+    }
+
+    #[test]
+    fn mempool_info() {
+        let response = r#"{"jsonrpc": "2.0", "result": {"mempoolminfee": 1e-05, "minrelaytxfee": 1e-05, "incrementalrelayfee": 1e-05}, "id": 0}"#;
+
+        let response: MempoolInfoResponse = serde_json::from_str(response).unwrap();
+        let expected = MempoolInfoResponse {
+            id: Id::Number(0),
+            info: MempoolInfo {
+                mempoolminfee: 0.00001,
+                minrelaytxfee: 0.00001,
+                incrementalrelayfee: 0.00001,
+            },
+        };
+        assert_eq!(response, expected);
+    }
+
+    #[test]
+    fn tx_broadcast_package() {
+        let response = r#"{"jsonrpc": "2.0", "result": {"success": true}, "id": 0}"#;
+        let response: TxBroadcastPackageResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.id, 0);
+        assert!(response.result.success);
+        assert!(response.result.errors.is_empty());
+
+        let response = r#"{"jsonrpc": "2.0", "result": {"success": false, "errors": [{"txid": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "error": "bad-txns-inputs-missingorspent"}]}, "id": 1}"#;
+        let response: TxBroadcastPackageResponse = serde_json::from_str(response).unwrap();
+        let expected = TxBroadcastPackageResponse {
+            id: Id::Number(1),
+            result: BroadcastPackageResult {
+                success: false,
+                errors: vec![PackageError {
+                    txid: Txid::from_str(
+                        "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681",
+                    )
+                    .unwrap(),
+                    error: "bad-txns-inputs-missingorspent".into(),
+                }],
+            },
+        };
+        assert_eq!(response, expected);
+    }
+
+    #[test]
+    fn parse_v1_6_responses() {
+        let mut index = HashMap::new();
+        let outpoint = OutPoint::from_str(
+            "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0:1",
+        )
+        .unwrap();
+        index.insert(Id::Number(0), Request::subscribe_outpoint(outpoint));
+        index.insert(Id::Number(1), Request::unsubscribe_outpoint(outpoint).id(1));
+        index.insert(Id::Number(2), Request::mempool_info().id(2));
+        index.insert(Id::Number(3), Request::tx_broadcast_package(vec![]).id(3));
+
+        let r = Response::try_parse(r#"{"jsonrpc": "2.0", "result": {}, "id": 0}"#, &index);
+        assert!(matches!(r, Ok(Response::OutpointSubscribe(_))));
+        let r = Response::try_parse(r#"{"jsonrpc": "2.0", "result": false, "id": 1}"#, &index);
+        assert!(matches!(r, Ok(Response::OutpointUnsubscribe(_))));
+        let r = Response::try_parse(
+            r#"{"jsonrpc": "2.0", "result": {"mempoolminfee": 1e-05, "minrelaytxfee": 1e-05, "incrementalrelayfee": 1e-05}, "id": 2}"#,
+            &index,
+        );
+        assert!(matches!(r, Ok(Response::MempoolInfo(_))));
+        let r = Response::try_parse(
+            r#"{"jsonrpc": "2.0", "result": {"success": true}, "id": 3}"#,
+            &index,
+        );
+        assert!(matches!(r, Ok(Response::TxBroadcastPackage(_))));
+    }
+
+    fn header_proof(index: usize) -> (Vec<String>, String) {
+        use crate::electrum::verify::tests::{header, merkle_tree, HEADERS};
+        use miniscript::bitcoin::hashes::Hash;
+
+        let leaves: Vec<_> = HEADERS
+            .iter()
+            .map(|h| sha256d::Hash::from_byte_array(header(h).block_hash().to_byte_array()))
+            .collect();
+        let (root, branch) = merkle_tree(&leaves, index);
+        (
+            branch.iter().map(|h| h.to_string()).collect(),
+            root.to_string(),
+        )
+    }
+
+    #[test]
+    fn header_with_proof() {
+        use crate::electrum::verify::tests::HEADERS;
+
+        let (branch, root) = header_proof(2);
+        let response = format!(
+            r#"{{"jsonrpc": "2.0", "result": {{"branch": {}, "header": "{}", "root": "{}"}}, "id": 0}}"#,
+            serde_json::to_string(&branch).unwrap(),
+            HEADERS[2],
+            root
+        );
+
+        let mut index = HashMap::new();
+        index.insert(Id::Number(0), Request::header_with_checkpoint(2, 4));
+        let response = Response::try_parse(&response, &index).unwrap();
+        let Response::HeaderWithProof(response) = response else {
+            panic!("wrong response")
+        };
+        assert_eq!(response.id, 0);
+        assert_eq!(response.header.branch, branch);
+
+        let checkpoint = Checkpoint::new(4, sha256d::Hash::from_str(&root).unwrap());
+        let header = response.header.verify(2, &checkpoint).unwrap();
+        assert_eq!(header, response.header.header().unwrap());
+        assert!(response.header.verify(1, &checkpoint).is_err());
+
+        // cp_height == 0 does not return a proof
+        index.insert(Id::Number(0), Request::header_with_checkpoint(2, 0));
+        let response = format!(
+            r#"{{"jsonrpc": "2.0", "result": "{}", "id": 0}}"#,
+            HEADERS[2]
+        );
+        assert!(matches!(
+            Response::try_parse(&response, &index),
+            Ok(Response::Header(_))
+        ));
+    }
+
+    #[test]
+    fn headers_with_proof() {
+        use crate::electrum::verify::tests::{header, HEADERS};
+        use miniscript::bitcoin::consensus::encode::serialize_hex;
+
+        let (branch, root) = header_proof(4);
+        let response = format!(
+            r#"{{"jsonrpc": "2.0", "result": {{"count": 3, "hex": "{}", "max": 2016, "branch": {}, "root": "{}"}}, "id": 0}}"#,
+            HEADERS[2..].concat(),
+            serde_json::to_string(&branch).unwrap(),
+            root
+        );
+
+        let mut index = HashMap::new();
+        index.insert(Id::Number(0), Request::headers_with_checkpoint(2, 3, 4));
+        let response = Response::try_parse(&response, &index).unwrap();
+        let Response::HeadersWithProof(response) = response else {
+            panic!("wrong response")
+        };
+        assert_eq!(response.headers.count, 3);
+
+        let checkpoint = Checkpoint::new(4, sha256d::Hash::from_str(&root).unwrap());
+        assert!(response.headers.verify(2, &checkpoint).is_ok());
+        assert!(response.headers.verify(1, &checkpoint).is_err());
+
+        // a tampered middle header is not committed by the checkpoint
+        let mut middle = header(HEADERS[3]);
+        middle.time += 1;
+        let mut tampered = HeadersWithProof {
+            raw_headers: [
+                HEADERS[2].to_string(),
+                serialize_hex(&middle),
+                HEADERS[4].to_string(),
+            ]
+            .concat(),
+            ..response.headers
+        };
+        assert!(tampered.verify(2, &checkpoint).is_err());
+        // & the linkage is checked even w/ a valid PoW
+        tampered.raw_headers = [HEADERS[2], HEADERS[2], HEADERS[4]].concat();
+        assert!(matches!(
+            tampered.verify(2, &checkpoint),
+            Err(Error::HeaderNotChained(1))
+        ));
+    }
+
+    #[test]
+    fn list_peers() {
+        let response = r#"{"jsonrpc": "2.0", "result": [["107.150.45.210", "e.anonyhost.org", ["v1.0", "p10000", "t", "s995"]], ["91.228.45.130", "electrum-fr.n2.net", ["v1.4.2", "t50001", "s"]], ["erbium1sgc2ghr7m3jwe7mkfxxu7tcmdayiwwcyekdxrd2rifmvc3fteoatad.onion", "erbium1sgc2ghr7m3jwe7mkfxxu7tcmdayiwwcyekdxrd2rifmvc3fteoatad.onion", ["v1.4", "s50002", "xunknown"]]], "id": 0}"#;
+
+        let response: ListPeersResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.id, 0);
+        assert_eq!(response.peers.len(), 3);
+        assert_eq!(
+            response.peers[0],
+            Peer {
+                ip: "107.150.45.210".into(),
+                hostname: "e.anonyhost.org".into(),
+                version: Some(ProtocolVersion::V1_0),
+                pruning: Some(10000),
+                tcp: Some(PeerPort::Default),
+                ssl: Some(PeerPort::Port(995)),
+            }
+        );
+        assert_eq!(
+            response.peers[1],
+            Peer {
+                ip: "91.228.45.130".into(),
+                hostname: "electrum-fr.n2.net".into(),
+                version: Some(ProtocolVersion::V1_4_2),
+                pruning: None,
+                tcp: Some(PeerPort::Port(50001)),
+                ssl: Some(PeerPort::Default),
+            }
+        );
+        assert_eq!(response.peers[2].version, Some(ProtocolVersion::V1_4));
+        assert_eq!(response.peers[2].tcp, None);
+        assert_eq!(response.peers[2].ssl, Some(PeerPort::Port(50002)));
+
+        // default ports follow the network
+        let peer = &response.peers[0];
+        assert_eq!(peer.tcp_port(Network::Bitcoin), Some(50001));
+        assert_eq!(peer.tcp_port(Network::Testnet), Some(60001));
+        assert_eq!(peer.ssl_port(Network::Testnet), Some(995));
+        assert_eq!(response.peers[1].ssl_port(Network::Signet), Some(60602));
+        assert_eq!(response.peers[2].tcp_port(Network::Bitcoin), None);
+
+        let serialized = serde_json::to_string(&response.peers[1]).unwrap();
+        assert_eq!(
+            serialized,
+            r#"["91.228.45.130","electrum-fr.n2.net",["v1.4.2","t50001","s"]]"#
+        );
+
+        // malformed features are ignored
+        let malformed = r#"{"jsonrpc": "2.0", "result": [["1.2.3.4", "toto", ["tabc", "vx", "pnone", "s", "s70000"]], ["5.6.7.8", "titi", ["v1.4"]]], "id": 0}"#;
+        let response = serde_json::from_str::<ListPeersResponse>(malformed).unwrap();
+        assert_eq!(response.peers.len(), 2);
+        assert_eq!(response.peers[0].tcp, None);
+        assert_eq!(response.peers[0].version, None);
+        assert_eq!(response.peers[0].pruning, None);
+        assert_eq!(response.peers[0].ssl, Some(PeerPort::Default));
+        assert_eq!(response.peers[1].version, Some(ProtocolVersion::V1_4));
+
+        let mut index = HashMap::new();
+        index.insert(Id::Number(0), Request::subscribe_peers());
+        let empty = r#"{"jsonrpc": "2.0", "result": [], "id": 0}"#;
+        assert_eq!(
+            Response::try_parse(empty, &index).unwrap(),
+            Response::ListPeers(ListPeersResponse {
+                id: Id::Number(0),
+                peers: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn add_peer() {
+        let response = r#"{"jsonrpc": "2.0", "result": true, "id": 0}"#;
+        let response: AddPeerResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(response.id, 0);
+        assert!(response.result);
+    }
+
+    fn dummy_tx() -> Transaction {
+        use miniscript::bitcoin::{
+            absolute::LockTime, transaction::Version, Sequence, TxIn, TxOut, Witness,
+        };
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::from_str(
+                    "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0:1",
+                )
+                .unwrap(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::from_slice(&[[0x01; 2]]),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(566888),
+                script_pubkey: ScriptBuf::from_hex("0014992f8cc4f6d284acac5f603e233592b566c04b2a")
+                    .unwrap(),
+            }],
+        }
+    }
+
+    #[test]
+    fn tx_get_raw() {
+        let tx = dummy_tx();
+        let txid = tx.compute_txid();
+        let raw = miniscript::bitcoin::consensus::encode::serialize_hex(&tx);
+        let response = format!(r#"{{"jsonrpc": "2.0", "result": "{}", "id": 0}}"#, raw);
+
+        let response: TxGetResponse = serde_json::from_str(&response).unwrap();
+        assert_eq!(response.result, TxGetResult::Raw(raw));
+        assert_eq!(response.result.transaction().unwrap(), tx);
+        assert_eq!(response.result.checked_transaction(&txid).unwrap(), tx);
+
+        let other =
+            Txid::from_str("9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681")
+                .unwrap();
+        assert!(matches!(
+            response.result.checked_transaction(&other),
+            Err(Error::TxidMismatch { expected, got }) if expected == other && got == txid
+        ));
+
+        let garbage = TxGetResult::Raw("0200zz".into());
+        assert!(matches!(
+            garbage.transaction(),
+            Err(Error::InvalidTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn tx_get_verbose() {
+        let tx = dummy_tx();
+        let txid = tx.compute_txid();
+        let raw = miniscript::bitcoin::consensus::encode::serialize_hex(&tx);
+        let address = Address::from_script(
+            &tx.output[0].script_pubkey,
+            miniscript::bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        let response = format!(
+            r#"{{"jsonrpc": "2.0", "result": {{"blockhash": "0000000000000000000245ca1b1ba9e2d3c96b0de0ef4fcbba6b8b0c7ff8d4ef", "blocktime": 1726827394, "confirmations": 12, "hash": "{wtxid}", "hex": "{raw}", "locktime": 0, "size": 113, "vsize": 86, "weight": 344, "time": 1726827394, "txid": "{txid}", "version": 2, "vin": [{{"scriptSig": {{"asm": "", "hex": ""}}, "sequence": 4294967293, "txid": "b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0", "txinwitness": ["0101"], "vout": 1}}], "vout": [{{"n": 0, "scriptPubKey": {{"address": "{address}", "asm": "0 992f8cc4f6d284acac5f603e233592b566c04b2a", "desc": "addr({address})#a4kz0cwy", "hex": "0014992f8cc4f6d284acac5f603e233592b566c04b2a", "type": "witness_v0_keyhash"}}, "value": 0.00566888}}]}}, "id": 0}}"#,
+            wtxid = tx.compute_wtxid(),
+            address = address,
+            raw = raw,
+            txid = txid,
+        );
+
+        let response: TxGetResponse = serde_json::from_str(&response).unwrap();
+        let TxGetResult::Verbose(verbose) = &response.result else {
+            panic!("wrong response")
+        };
+        assert!(verbose.is_confirmed());
+        assert_eq!(verbose.confirmations, Some(12));
+        assert_eq!(verbose.txid, txid);
+        assert_eq!(verbose.hash, Some(tx.compute_wtxid()));
+        assert_eq!(verbose.vin.len(), 1);
+        assert!(!verbose.vin[0].is_coinbase());
+        assert_eq!(
+            verbose.vin[0].previous_output(),
+            Some(tx.input[0].previous_output)
+        );
+        assert_eq!(verbose.vin[0].witness, vec!["0101".to_string()]);
+        assert_eq!(verbose.vout.len(), 1);
+        let out = &verbose.vout[0];
+        assert_eq!(out.value, Amount::from_sat(566888));
+        assert_eq!(out.script_pubkey.script_type, ScriptType::P2wpkh);
+        assert_eq!(out.script_pubkey.hex, tx.output[0].script_pubkey);
+        let address = out.script_pubkey.address.clone().unwrap();
+        assert_eq!(
+            address.clone().assume_checked().script_pubkey(),
+            tx.output[0].script_pubkey
+        );
+        assert_eq!(
+            out.script_pubkey.checked_address(Network::Bitcoin).unwrap(),
+            Some(address.assume_checked())
+        );
+        assert!(matches!(
+            out.script_pubkey.checked_address(Network::Testnet),
+            Err(Error::InvalidAddress(_))
+        ));
+        assert!(verbose.check_addresses(Network::Bitcoin).is_ok());
+        assert!(verbose.check_addresses(Network::Testnet).is_err());
+        assert_eq!(response.result.checked_transaction(&txid).unwrap(), tx);
+    }
+
+    #[test]
+    fn tx_get_verbose_mempool_coinbase() {
+        // NOTE: we do not check the raw tx here
+        let response = r#"{"jsonrpc": "2.0", "result": {"hex": "00", "locktime": 0, "size": 100, "txid": "9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681", "version": 1, "vin": [{"coinbase": "03a0bb0d", "sequence": 4294967295}], "vout": [{"n": 0, "scriptPubKey": {"asm": "OP_RETURN aa21a9ed", "hex": "6a04aa21a9ed", "type": "nulldata"}, "value": 0.0}, {"n": 1, "scriptPubKey": {"asm": "", "hex": "", "type": "some_future_type"}, "value": 0.0}]}, "id": 0}"#;
+
+        let response: TxGetResponse = serde_json::from_str(response).unwrap();
+        let TxGetResult::Verbose(verbose) = &response.result else {
+            panic!("wrong response")
+        };
+        assert!(!verbose.is_confirmed());
+        assert!(verbose.blockhash.is_none());
+        assert!(verbose.vin[0].is_coinbase());
+        assert!(verbose.vin[0].previous_output().is_none());
+        assert_eq!(
+            verbose.vout[0].script_pubkey.script_type,
+            ScriptType::NullData
+        );
+        assert!(verbose.vout[0].script_pubkey.address.is_none());
+        assert_eq!(
+            verbose.vout[1].script_pubkey.script_type,
+            ScriptType::NonStandard
+        );
+    }
+
+    #[test]
+    fn invalid_headers() {
+        let headers = Headers {
+            count: 2,
+            raw_headers: "00".repeat(80),
+            max: 2016,
+        };
+        assert!(matches!(headers.headers(), Err(Error::InvalidHeader(_))));
+
+        let headers = Headers {
+            count: 0,
+            raw_headers: "".into(),
+            max: 2016,
+        };
+        assert!(headers.headers().unwrap().is_empty());
+
+        let header = HeaderResponse {
+            id: Id::Number(0),
+            raw_header: "zz".repeat(80),
+        };
+        assert!(matches!(header.header(), Err(Error::InvalidHeader(_))));
+    }
+
+    // parse `raw` as a response to `request`, serialize it back and compare
+    fn roundtrip(raw: &str, request: Request) {
+        let mut index = HashMap::new();
+        index.insert(request.id.clone(), request);
+        let response = Response::try_parse(raw, &index).unwrap();
+        let serialized = serde_json::to_value(&response).unwrap();
+        let expected: Value = serde_json::from_str(raw).unwrap();
+        assert_eq!(serialized, expected, "{}", raw);
+    }
+
+    #[test]
+    fn serialize_response() {
+        let script = Script::from_bytes(&[0x00]);
+        let txid =
+            Txid::from_str("9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681")
+                .unwrap();
+        roundtrip(r#"{"jsonrpc":"2.0","id":0,"result":null}"#, Request::ping());
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":3,"result":{"height":119367,"hex":"00000020835fdbdeeadd23463fad98b4e21aaa8519afde89eecd0eb224001317421cbb5f5e636df02303e51280b586bc596ee9326bc849bbb5993e121a8cab7e6b60e8ab593fe166ffff7f2000000000"}}"#,
+            Request::subscribe_headers().id(3),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","method":"blockchain.scripthash.subscribe","params":["95ebd95e7c0763b785d12b1d20d9f548fa5bb809f120afb0dd11276fa1ce8352","9bf1d98ff899eafd048290199144aed63e3d7ccbc8925e8351a4c1e8af2137f4"]}"#,
+            Request::ping(),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":{"confirmed":566888,"unconfirmed":-1000}}"#,
+            Request::sh_get_balance(script),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":[{"tx_hash":"b14edd61d6902890932be0d4386c79ca64a8dea345e9b9c95b2e8a825316cfc0","height":861250},{"tx_hash":"9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681","height":-1,"fee":250}]}"#,
+            Request::sh_get_history(script),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":[{"tx_hash":"9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681","tx_pos":0,"height":0,"value":1000}]}"#,
+            Request::sh_list_unspent(script),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"error":{"code":1,"message":"unsupported request"}}"#,
+            Request::ping(),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":"9cc064bbce74a2c56ce12b0b59fc7267a2618a35e1d8c66f642efd6d033a9681"}"#,
+            Request::tx_broadcast("00".into()),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":{"block_height":10,"merkle":["e48b08df0afa01a7339335fb6b6964100d11985765cbc6afcde990fd65856a9b"],"pos":1}}"#,
+            Request::tx_get_merkle(txid, 10),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":["electrs/0.10.5","1.4"]}"#,
+            Request::version("toto".into(), "1.4".into()),
+        );
+        roundtrip(
+            r#"{"jsonrpc":"2.0","id":0,"result":[["107.150.45.210","e.anonyhost.org",["v1.0","p10000","t50001","s995"]]]}"#,
+            Request::subscribe_peers(),
+        );
+
+        // batch
+        let mut index = HashMap::new();
+        index.insert(Id::Number(1), Request::ping().id(1));
+        index.insert(Id::Number(2), Request::ping().id(2));
+        let raw =
+            r#"[{"jsonrpc":"2.0","id":1,"result":null},{"jsonrpc":"2.0","id":2,"result":null}]"#;
+        let batch = ResponseBatch::from_str(raw, &index).unwrap();
+        assert_eq!(serde_json::to_string(&batch).unwrap(), raw);
+    }
 }
@@ -0,0 +1,346 @@
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use miniscript::{
+    bitcoin::{
+        secp256k1::{Secp256k1, Verification},
+        Address, Network, Script, ScriptBuf,
+    },
+    DefiniteDescriptorKey, Descriptor, DescriptorPublicKey,
+};
+
+use super::{response::SHNotification, types::ScriptHash, Error};
+
+/// Types that can be converted into the script hash used by the
+/// `blockchain.scripthash.*` methods.
+pub trait ToScriptHash {
+    fn to_script_hash(&self) -> ScriptHash;
+}
+
+impl<T: ToScriptHash + ?Sized> ToScriptHash for &T {
+    fn to_script_hash(&self) -> ScriptHash {
+        (**self).to_script_hash()
+    }
+}
+
+impl ToScriptHash for ScriptHash {
+    fn to_script_hash(&self) -> ScriptHash {
+        *self
+    }
+}
+
+impl ToScriptHash for Script {
+    fn to_script_hash(&self) -> ScriptHash {
+        ScriptHash::new(self)
+    }
+}
+
+impl ToScriptHash for ScriptBuf {
+    fn to_script_hash(&self) -> ScriptHash {
+        ScriptHash::new(self)
+    }
+}
+
+impl ToScriptHash for Address {
+    fn to_script_hash(&self) -> ScriptHash {
+        ScriptHash::new(&self.script_pubkey())
+    }
+}
+
+impl ToScriptHash for Descriptor<DefiniteDescriptorKey> {
+    fn to_script_hash(&self) -> ScriptHash {
+        ScriptHash::new(&self.script_pubkey())
+    }
+}
+
+fn derive_with<C: Verification>(
+    secp: &Secp256k1<C>,
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    index: u32,
+) -> Result<ScriptBuf, Error> {
+    descriptor
+        .derived_descriptor(secp, index)
+        .map(|d| d.script_pubkey())
+        .map_err(|e| Error::InvalidDescriptor(e.to_string()))
+}
+
+/// Derive the script pubkey of `descriptor` at `index`.
+pub fn derive_script(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    index: u32,
+) -> Result<ScriptBuf, Error> {
+    derive_with(&Secp256k1::verification_only(), descriptor, index)
+}
+
+/// Derive the script pubkeys of `descriptor` for every index of `range`, a
+/// descriptor w/o wildcard only yield its script at index 0.
+pub fn derive_scripts(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    range: Range<u32>,
+) -> Result<Vec<(u32, ScriptBuf)>, Error> {
+    if descriptor.is_multipath() {
+        return Err(Error::InvalidDescriptor(
+            "multipath descriptors must be split first".into(),
+        ));
+    }
+    let range = if descriptor.has_wildcard() {
+        range
+    } else {
+        0..1
+    };
+    let secp = Secp256k1::verification_only();
+    range
+        .map(|index| derive_with(&secp, descriptor, index).map(|s| (index, s)))
+        .collect()
+}
+
+/// Where a script registered in a [`ScriptRegistry`] comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptOrigin {
+    Script,
+    Address(Address),
+    Descriptor {
+        // NOTE: shared by all the scripts derived from the descriptor
+        descriptor: Arc<Descriptor<DescriptorPublicKey>>,
+        index: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptInfo {
+    pub script: ScriptBuf,
+    pub origin: ScriptOrigin,
+}
+
+impl ScriptInfo {
+    pub fn address(&self, network: Network) -> Option<Address> {
+        match &self.origin {
+            ScriptOrigin::Address(a) => Some(a.clone()),
+            _ => Address::from_script(&self.script, network).ok(),
+        }
+    }
+
+    /// The descriptor and derivation index of the script, if any.
+    pub fn derivation(&self) -> Option<(&Descriptor<DescriptorPublicKey>, u32)> {
+        match &self.origin {
+            ScriptOrigin::Descriptor { descriptor, index } => Some((descriptor.as_ref(), *index)),
+            _ => None,
+        }
+    }
+}
+
+/// Map the script hashes received from the server back to the scripts they
+/// have been derived from.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptRegistry {
+    scripts: HashMap<ScriptHash, ScriptInfo>,
+}
+
+impl ScriptRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&mut self, script: ScriptBuf, origin: ScriptOrigin) -> ScriptHash {
+        let sh = ScriptHash::new(&script);
+        self.scripts.insert(sh, ScriptInfo { script, origin });
+        sh
+    }
+
+    pub fn insert_script(&mut self, script: &Script) -> ScriptHash {
+        self.insert(script.into(), ScriptOrigin::Script)
+    }
+
+    pub fn insert_address(&mut self, address: Address) -> ScriptHash {
+        self.insert(address.script_pubkey(), ScriptOrigin::Address(address))
+    }
+
+    /// Register the scripts of `descriptor` for every index of `range`, see
+    /// [`derive_scripts()`].
+    pub fn insert_descriptor(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        range: Range<u32>,
+    ) -> Result<Vec<ScriptHash>, Error> {
+        let scripts = derive_scripts(descriptor, range)?;
+        let descriptor = Arc::new(descriptor.clone());
+        Ok(scripts
+            .into_iter()
+            .map(|(index, script)| {
+                let origin = ScriptOrigin::Descriptor {
+                    descriptor: descriptor.clone(),
+                    index,
+                };
+                self.insert(script, origin)
+            })
+            .collect())
+    }
+
+    pub fn get(&self, sh: &ScriptHash) -> Option<&ScriptInfo> {
+        self.scripts.get(sh)
+    }
+
+    pub fn script(&self, sh: &ScriptHash) -> Option<&Script> {
+        self.get(sh).map(|i| i.script.as_script())
+    }
+
+    /// Lookup the script a `blockchain.scripthash.subscribe` notification is about.
+    pub fn notification(&self, notif: &SHNotification) -> Option<&ScriptInfo> {
+        self.get(&notif.script_hash())
+    }
+
+    pub fn remove(&mut self, sh: &ScriptHash) -> Option<ScriptInfo> {
+        self.scripts.remove(sh)
+    }
+
+    pub fn contains(&self, sh: &ScriptHash) -> bool {
+        self.scripts.contains_key(sh)
+    }
+
+    pub fn script_hashes(&self) -> impl Iterator<Item = &ScriptHash> {
+        self.scripts.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use miniscript::bitcoin::address::NetworkUnchecked;
+
+    use super::*;
+    use crate::electrum::request::Request;
+
+    const DESCRIPTOR: &str = "wpkh(tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/0/*)";
+
+    fn descriptor(s: &str) -> Descriptor<DescriptorPublicKey> {
+        Descriptor::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn to_script_hash() {
+        let descriptor = descriptor(DESCRIPTOR);
+        let definite = descriptor.at_derivation_index(3).unwrap();
+        let script = definite.script_pubkey();
+        let address = definite.address(Network::Regtest).unwrap();
+        let sh = ScriptHash::new(&script);
+
+        assert_eq!(script.to_script_hash(), sh);
+        assert_eq!(script.as_script().to_script_hash(), sh);
+        assert_eq!(sh.to_script_hash(), sh);
+        assert_eq!(address.to_script_hash(), sh);
+        assert_eq!(definite.to_script_hash(), sh);
+        assert_eq!(derive_script(&descriptor, 3).unwrap(), script);
+
+        // all the constructors produce the same request
+        let expected = serde_json::to_string(&Request::sh_get_balance(script.as_script())).unwrap();
+        for request in [
+            Request::sh_get_balance(&script),
+            Request::sh_get_balance(sh),
+            Request::sh_get_balance(&address),
+            Request::sh_get_balance(&definite),
+        ] {
+            assert_eq!(serde_json::to_string(&request).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn derive() {
+        let ranged = descriptor(DESCRIPTOR);
+        let scripts = derive_scripts(&ranged, 5..10).unwrap();
+        assert_eq!(scripts.len(), 5);
+        assert_eq!(scripts[0].0, 5);
+        assert_eq!(scripts[0].1, derive_script(&ranged, 5).unwrap());
+
+        let requests = Request::for_descriptor(&ranged, 0..20, Request::subscribe_sh).unwrap();
+        assert_eq!(requests.len(), 20);
+
+        let single =
+            descriptor("wpkh(02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443)");
+        assert_eq!(derive_scripts(&single, 0..20).unwrap().len(), 1);
+
+        let multipath = descriptor("wpkh(tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/<0;1>/*)");
+        assert!(matches!(
+            derive_scripts(&multipath, 0..2),
+            Err(Error::InvalidDescriptor(_))
+        ));
+
+        // hardened derivation is not possible from an xpub
+        let hardened = descriptor("wpkh(tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/0/*')");
+        assert!(matches!(
+            derive_scripts(&hardened, 0..2),
+            Err(Error::InvalidDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn registry() {
+        let descriptor = descriptor(DESCRIPTOR);
+        let mut registry = ScriptRegistry::new();
+        assert!(registry.is_empty());
+
+        let hashes = registry.insert_descriptor(&descriptor, 0..10).unwrap();
+        assert_eq!(registry.len(), 10);
+        let info = registry.get(&hashes[7]).unwrap();
+        assert_eq!(info.derivation(), Some((&descriptor, 7)));
+        match (&info.origin, &registry.get(&hashes[3]).unwrap().origin) {
+            (
+                ScriptOrigin::Descriptor { descriptor: a, .. },
+                ScriptOrigin::Descriptor { descriptor: b, .. },
+            ) => assert!(Arc::ptr_eq(a, b)),
+            _ => panic!("expected descriptor origins"),
+        }
+        assert_eq!(info.script, derive_script(&descriptor, 7).unwrap());
+        assert_eq!(
+            info.address(Network::Regtest),
+            Some(
+                descriptor
+                    .at_derivation_index(7)
+                    .unwrap()
+                    .address(Network::Regtest)
+                    .unwrap()
+            )
+        );
+
+        let address: Address<NetworkUnchecked> = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"
+            .parse()
+            .unwrap();
+        let address = address.assume_checked();
+        let sh = registry.insert_address(address.clone());
+        assert_eq!(
+            registry.get(&sh).unwrap().origin,
+            ScriptOrigin::Address(address.clone())
+        );
+        assert_eq!(
+            registry.script(&sh),
+            Some(address.script_pubkey().as_script())
+        );
+        assert!(registry.get(&sh).unwrap().derivation().is_none());
+
+        let script = ScriptBuf::from_bytes(vec![0x51]);
+        let sh = registry.insert_script(&script);
+        assert_eq!(registry.get(&sh).unwrap().origin, ScriptOrigin::Script);
+        assert_eq!(registry.len(), 12);
+
+        let notif = format!(
+            r#"{{"jsonrpc":"2.0","method":"blockchain.scripthash.subscribe","params":["{}",null]}}"#,
+            hashes[3]
+        );
+        let notif = SHNotification::from_str(&notif).unwrap();
+        assert_eq!(
+            registry.notification(&notif).unwrap().derivation(),
+            Some((&descriptor, 3))
+        );
+
+        assert!(registry.remove(&hashes[3]).is_some());
+        assert!(!registry.contains(&hashes[3]));
+        assert!(registry.notification(&notif).is_none());
+    }
+}
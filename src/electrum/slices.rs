@@ -0,0 +1,262 @@
+use std::ops::ControlFlow;
+
+use bitcoin_slices::{bsl, EmptyVisitor, Visit};
+use miniscript::bitcoin::{
+    hashes::{sha256d, Hash, HashEngine},
+    Amount, BlockHash, CompactTarget, Script, TxMerkleNode, Txid,
+};
+
+pub use bitcoin_slices::Visitor;
+
+use super::{types::HEADER_ROW_SIZE, Error};
+
+/// Decode hex encoded transactions & headers into a reusable buffer & walk
+/// them w/ [`bitcoin_slices`] visitors, w/o building `Transaction` or
+/// `BlockHeader` objects.
+#[derive(Debug, Default)]
+pub struct SliceDecoder {
+    buf: Vec<u8>,
+}
+
+impl SliceDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Decode `hex` into the internal buffer, the previous content is
+    /// discarded but its allocation is kept.
+    pub fn decode(&mut self, hex: &str) -> Option<&[u8]> {
+        self.buf.clear();
+        let pairs = hex.as_bytes().chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return None;
+        }
+        self.buf.reserve(pairs.len());
+        for pair in pairs {
+            self.buf.push(nibble(pair[0])? << 4 | nibble(pair[1])?);
+        }
+        Some(&self.buf)
+    }
+
+    /// Visit the transaction encoded in `hex` & return its txid.
+    pub fn visit_tx<V: Visitor>(&mut self, hex: &str, visitor: &mut V) -> Result<Txid, Error> {
+        let invalid = || Error::InvalidTransaction(hex.into());
+        let bytes = self.decode(hex).ok_or_else(invalid)?;
+        let parsed = bsl::Transaction::visit(bytes, visitor).map_err(|_| invalid())?;
+        if !parsed.remaining().is_empty() {
+            return Err(invalid());
+        }
+        Ok(txid(parsed.parsed()))
+    }
+
+    /// Compute the txid of the transaction encoded in `hex`.
+    pub fn txid(&mut self, hex: &str) -> Result<Txid, Error> {
+        self.visit_tx(hex, &mut EmptyVisitor {})
+    }
+
+    /// Call `f` w/ the index, the value & the scriptPubKey of every output of
+    /// the transaction encoded in `hex`, returns its txid.
+    pub fn tx_outputs<F>(&mut self, hex: &str, f: F) -> Result<Txid, Error>
+    where
+        F: FnMut(usize, Amount, &Script),
+    {
+        self.visit_tx(hex, &mut OutputVisitor(f))
+    }
+
+    /// Call `f` w/ every header of `hex` (concatenated 80 bytes headers as
+    /// returned by `blockchain.block.headers`), returns the number of headers.
+    pub fn headers<F>(&mut self, hex: &str, mut f: F) -> Result<usize, Error>
+    where
+        F: FnMut(&HeaderSlice<'_>),
+    {
+        let invalid = || Error::InvalidHeader(hex.into());
+        let bytes = self.decode(hex).ok_or_else(invalid)?;
+        let chunks = bytes.chunks_exact(HEADER_ROW_SIZE);
+        if !chunks.remainder().is_empty() {
+            return Err(invalid());
+        }
+        for chunk in chunks {
+            let parsed =
+                bsl::BlockHeader::visit(chunk, &mut EmptyVisitor {}).map_err(|_| invalid())?;
+            f(&HeaderSlice(parsed.parsed_owned()));
+        }
+        Ok(bytes.len() / HEADER_ROW_SIZE)
+    }
+}
+
+fn nibble(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+struct OutputVisitor<F>(F);
+
+impl<F: FnMut(usize, Amount, &Script)> Visitor for OutputVisitor<F> {
+    fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<()> {
+        (self.0)(
+            vout,
+            Amount::from_sat(tx_out.value()),
+            Script::from_bytes(tx_out.script_pubkey()),
+        );
+        ControlFlow::Continue(())
+    }
+}
+
+/// Compute the txid of a transaction slice.
+pub fn txid(tx: &bsl::Transaction) -> Txid {
+    let (version, body, locktime) = tx.txid_preimage();
+    let mut engine = sha256d::Hash::engine();
+    engine.input(version);
+    engine.input(body);
+    engine.input(locktime);
+    Txid::from_raw_hash(sha256d::Hash::from_engine(engine))
+}
+
+/// A block header borrowed from a [`SliceDecoder`] buffer.
+pub struct HeaderSlice<'a>(bsl::BlockHeader<'a>);
+
+impl HeaderSlice<'_> {
+    pub fn block_hash(&self) -> BlockHash {
+        BlockHash::from_raw_hash(sha256d::Hash::hash(self.0.block_hash_preimage()))
+    }
+
+    pub fn version(&self) -> i32 {
+        self.0.version()
+    }
+
+    pub fn prev_blockhash(&self) -> BlockHash {
+        BlockHash::from_slice(self.0.prev_blockhash()).expect("32 bytes")
+    }
+
+    pub fn merkle_root(&self) -> TxMerkleNode {
+        TxMerkleNode::from_slice(self.0.merkle_root()).expect("32 bytes")
+    }
+
+    pub fn time(&self) -> u32 {
+        self.0.time()
+    }
+
+    pub fn bits(&self) -> CompactTarget {
+        let bytes = &self.0.block_hash_preimage()[72..76];
+        CompactTarget::from_consensus(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    pub fn nonce(&self) -> u32 {
+        self.0.nonce()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::electrum::verify::tests::{header, HEADERS};
+    use miniscript::bitcoin::{
+        absolute::LockTime, consensus::encode::serialize_hex, transaction::Version, OutPoint,
+        ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+    };
+
+    fn tx(witness: bool) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array([1; 32]), 3),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: if witness {
+                    Witness::from_slice(&[vec![2u8; 72], vec![3u8; 33]])
+                } else {
+                    Witness::new()
+                },
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(1000),
+                    script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+                },
+                TxOut {
+                    value: Amount::from_sat(2000),
+                    script_pubkey: ScriptBuf::from_bytes(vec![0x00, 0x14, 0x42]),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn transaction() {
+        let mut decoder = SliceDecoder::new();
+        for tx in [tx(false), tx(true)] {
+            let hex = serialize_hex(&tx);
+            assert_eq!(decoder.txid(&hex).unwrap(), tx.compute_txid());
+
+            let mut outputs = Vec::new();
+            let txid = decoder
+                .tx_outputs(&hex, |vout, value, spk| {
+                    outputs.push((vout, value, spk.to_owned()))
+                })
+                .unwrap();
+            assert_eq!(txid, tx.compute_txid());
+            let expected: Vec<_> = tx
+                .output
+                .iter()
+                .enumerate()
+                .map(|(i, o)| (i, o.value, o.script_pubkey.clone()))
+                .collect();
+            assert_eq!(outputs, expected);
+        }
+
+        let hex = serialize_hex(&tx(true));
+        assert!(decoder.txid(&hex[..hex.len() - 2]).is_err());
+        assert!(decoder.txid(&format!("{}00", hex)).is_err());
+        assert!(decoder.txid(&hex[1..]).is_err());
+        assert!(decoder.txid("zz").is_err());
+    }
+
+    #[test]
+    fn headers() {
+        let mut decoder = SliceDecoder::new();
+        let raw = HEADERS.concat();
+        let mut decoded = Vec::new();
+        let count = decoder
+            .headers(&raw, |h| {
+                let expected = header(HEADERS[decoded.len()]);
+                assert_eq!(h.block_hash(), expected.block_hash());
+                assert_eq!(h.version(), expected.version.to_consensus());
+                assert_eq!(h.prev_blockhash(), expected.prev_blockhash);
+                assert_eq!(h.merkle_root(), expected.merkle_root);
+                assert_eq!(h.time(), expected.time);
+                assert_eq!(h.bits(), expected.bits);
+                assert_eq!(h.nonce(), expected.nonce);
+                decoded.push(h.block_hash());
+            })
+            .unwrap();
+        assert_eq!(count, HEADERS.len());
+        // chained
+        let mut prev = None;
+        decoder
+            .headers(&raw, |h| {
+                if let Some(prev) = prev {
+                    assert_eq!(h.prev_blockhash(), prev);
+                }
+                prev = Some(h.block_hash());
+            })
+            .unwrap();
+
+        assert_eq!(decoder.headers("", |_| {}).unwrap(), 0);
+        assert!(decoder.headers(&raw[..raw.len() - 2], |_| {}).is_err());
+    }
+}
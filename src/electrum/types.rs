@@ -2,11 +2,19 @@
 use miniscript::bitcoin::{
     blockdata::block::Header as BlockHeader,
     consensus::encode::{deserialize, Decodable, Encodable},
-    hashes::{hash_newtype, sha256, Hash},
+    hashes::{hash_newtype, sha256, Hash, HashEngine},
     OutPoint, Script, Txid,
 };
-use miniscript::serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
+
+use super::response::HistoryResult;
+use miniscript::serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+};
 
 use miniscript::bitcoin::io;
 macro_rules! impl_consensus_encoding {
@@ -113,6 +121,106 @@ hash_newtype! {
     pub struct StatusHash(sha256::Hash);
 }
 
+impl StatusHash {
+    /// Compute the status of a script hash from its history, the history must
+    /// be ordered as returned by `blockchain.scripthash.get_history` (see
+    /// [`super::response::sort_history()`]), `None` if the history is empty.
+    pub fn from_history(history: &[HistoryResult]) -> Option<Self> {
+        if history.is_empty() {
+            return None;
+        }
+        let mut engine = Self::engine();
+        for tx in history {
+            engine.input(format!("{}:{}:", tx.txid, tx.height).as_bytes());
+        }
+        Some(Self::from_engine(engine))
+    }
+}
+
+// ***************************************************************************
+
+/// A JSON-RPC request id, a number or a string: some proxies in front of
+/// Electrum servers rewrite ids to strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(untagged)]
+pub enum Id {
+    Number(usize),
+    String(String),
+}
+
+impl Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Id::Number(n) => write!(f, "{}", n),
+            Id::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl From<usize> for Id {
+    fn from(value: usize) -> Self {
+        Id::Number(value)
+    }
+}
+
+impl From<String> for Id {
+    fn from(value: String) -> Self {
+        Id::String(value)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(value: &str) -> Self {
+        Id::String(value.into())
+    }
+}
+
+impl PartialEq<usize> for Id {
+    fn eq(&self, other: &usize) -> bool {
+        matches!(self, Id::Number(n) if n == other)
+    }
+}
+
+// NOTE: not derived w/ `untagged` as it would buffer the value
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct IdVisitor;
+
+        impl<'de> Visitor<'de> for IdVisitor {
+            type Value = Id;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a positive integer or a string")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Id, E> {
+                usize::try_from(v)
+                    .map(Id::Number)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Id, E> {
+                usize::try_from(v)
+                    .map(Id::Number)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Id, E> {
+                Ok(Id::String(v.into()))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Id, E> {
+                Ok(Id::String(v))
+            }
+        }
+
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
+
 // ***************************************************************************
 
 fn spending_prefix(prev: OutPoint) -> HashPrefix {
@@ -162,11 +270,11 @@ impl TxidRow {
 
 // ***************************************************************************
 
-pub(crate) type SerializedHeaderRow = [u8; HEADER_ROW_SIZE];
+pub type SerializedHeaderRow = [u8; HEADER_ROW_SIZE];
 
 #[derive(Debug, Serialize, Deserialize)]
-pub(crate) struct HeaderRow {
-    pub(crate) header: BlockHeader,
+pub struct HeaderRow {
+    pub header: BlockHeader,
 }
 
 pub const HEADER_ROW_SIZE: usize = 80;
@@ -174,11 +282,11 @@ pub const HEADER_ROW_SIZE: usize = 80;
 impl_consensus_encoding!(HeaderRow, header);
 
 impl HeaderRow {
-    pub(crate) fn new(header: BlockHeader) -> Self {
+    pub fn new(header: BlockHeader) -> Self {
         Self { header }
     }
 
-    pub(crate) fn to_db_row(&self) -> SerializedHeaderRow {
+    pub fn to_db_row(&self) -> SerializedHeaderRow {
         let mut row = [0; HEADER_ROW_SIZE];
         let len = self
             .consensus_encode(&mut (&mut row as &mut [u8]))
@@ -187,7 +295,7 @@ impl HeaderRow {
         row
     }
 
-    pub(crate) fn from_db_row(row: SerializedHeaderRow) -> Self {
+    pub fn from_db_row(row: SerializedHeaderRow) -> Self {
         deserialize(&row).expect("bad HeaderRow")
     }
 }
@@ -195,7 +303,7 @@ impl HeaderRow {
 #[cfg(test)]
 mod tests {
     use crate::electrum::types::{
-        spending_prefix, HashPrefixRow, ScriptHash, ScriptHashRow, TxidRow,
+        spending_prefix, HashPrefixRow, Id, ScriptHash, ScriptHashRow, TxidRow,
     };
     use hex_lit::hex;
     use miniscript::bitcoin::{Address, OutPoint, Txid};
@@ -211,6 +319,20 @@ mod tests {
         assert_eq!(json!(scripthash).to_string(), hex);
     }
 
+    #[test]
+    fn test_id_serde() {
+        assert_eq!(from_str::<Id>("7").unwrap(), Id::Number(7));
+        assert_eq!(from_str::<Id>("\"7\"").unwrap(), Id::String("7".into()));
+        assert_eq!(from_str::<Id>("\"6f1c-aa\"").unwrap(), Id::from("6f1c-aa"));
+        assert!(from_str::<Id>("-1").is_err());
+        assert!(from_str::<Id>("1.5").is_err());
+        assert_eq!(from_str::<Option<Id>>("null").unwrap(), None);
+        assert_eq!(json!(Id::Number(7)).to_string(), "7");
+        assert_eq!(json!(Id::from("a")).to_string(), "\"a\"");
+        assert_eq!(Id::Number(7), 7);
+        assert_ne!(Id::from("7"), 7);
+    }
+
     #[test]
     fn test_scripthash_row() {
         let hex = "\"4b3d912c1523ece4615e91bf0d27381ca72169dbf6b1c2ffcc9f92381d4984a3\"";
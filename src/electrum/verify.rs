@@ -0,0 +1,394 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use miniscript::bitcoin::{
+    block::Header as BlockHeader,
+    hashes::{sha256d, Hash, HashEngine},
+    merkle_tree, TxMerkleNode, Txid,
+};
+
+use super::Error;
+
+/// Rebuild a merkle root from a leaf, its position in the tree and the
+/// merkle branch returned by the server.
+pub fn merkle_root_from_branch(
+    leaf: sha256d::Hash,
+    branch: &[sha256d::Hash],
+    mut index: usize,
+) -> sha256d::Hash {
+    let mut hash = leaf;
+    for node in branch {
+        let mut engine = sha256d::Hash::engine();
+        if index & 1 == 1 {
+            engine.input(node.as_byte_array());
+            engine.input(hash.as_byte_array());
+        } else {
+            engine.input(hash.as_byte_array());
+            engine.input(node.as_byte_array());
+        }
+        hash = sha256d::Hash::from_engine(engine);
+        index >>= 1;
+    }
+    hash
+}
+
+/// Check that `txid` is at position `pos` in the block of `header`.
+pub fn verify_tx_inclusion(
+    txid: &Txid,
+    branch: &[TxMerkleNode],
+    pos: usize,
+    header: &BlockHeader,
+) -> Result<(), Error> {
+    // NOTE: the bits of `pos` above the depth of the branch would be ignored
+    // when rebuilding the root, so any position could be claimed
+    let depth = u32::try_from(branch.len()).unwrap_or(u32::MAX);
+    if pos.checked_shr(depth).unwrap_or(0) != 0 {
+        return Err(Error::InvalidProof);
+    }
+    let branch: Vec<_> = branch.iter().map(|node| node.to_raw_hash()).collect();
+    let root = merkle_root_from_branch(txid.to_raw_hash(), &branch, pos);
+    if TxMerkleNode::from_raw_hash(root) == header.merkle_root {
+        Ok(())
+    } else {
+        Err(Error::InvalidProof)
+    }
+}
+
+/// Check that `txids` are all the transactions of the block of `header`, in
+/// block order.
+pub fn verify_block_txids(txids: &[Txid], header: &BlockHeader) -> Result<(), Error> {
+    // NOTE: duplicating the tail of the list keeps the same root
+    // (CVE-2012-2459), a block never holds the same txid twice
+    let mut seen = HashSet::with_capacity(txids.len());
+    if !txids.iter().all(|txid| seen.insert(txid)) {
+        return Err(Error::InvalidProof);
+    }
+    let root = merkle_tree::calculate_root(
+        txids
+            .iter()
+            .map(|txid| TxMerkleNode::from_raw_hash(txid.to_raw_hash())),
+    );
+    if root == Some(header.merkle_root) {
+        Ok(())
+    } else {
+        Err(Error::InvalidProof)
+    }
+}
+
+/// Compute the merkle branch of the transaction at `pos` in a block
+/// containing `txids`, `None` if `pos` is out of range.
+pub fn merkle_branch(txids: &[Txid], mut pos: usize) -> Option<Vec<TxMerkleNode>> {
+    if pos >= txids.len() {
+        return None;
+    }
+    let mut level: Vec<_> = txids.iter().map(|txid| txid.to_raw_hash()).collect();
+    let mut branch = Vec::new();
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(*level.last().expect("not empty"));
+        }
+        branch.push(TxMerkleNode::from_raw_hash(level[pos ^ 1]));
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut engine = sha256d::Hash::engine();
+                engine.input(pair[0].as_byte_array());
+                engine.input(pair[1].as_byte_array());
+                sha256d::Hash::from_engine(engine)
+            })
+            .collect();
+        pos >>= 1;
+    }
+    Some(branch)
+}
+
+/// A source of trusted headers indexed by height, e.g. a local header store.
+pub trait HeaderSource {
+    fn header_at(&self, height: usize) -> Option<BlockHeader>;
+}
+
+impl HeaderSource for HashMap<usize, BlockHeader> {
+    fn header_at(&self, height: usize) -> Option<BlockHeader> {
+        self.get(&height).copied()
+    }
+}
+
+impl HeaderSource for BTreeMap<usize, BlockHeader> {
+    fn header_at(&self, height: usize) -> Option<BlockHeader> {
+        self.get(&height).copied()
+    }
+}
+
+/// Check that each header commits to the previous one and that its
+/// proof-of-work meets the target it claims, `prev` is the header preceding
+/// `headers[0]` if known.
+/// NOTE: difficulty adjustments are not checked.
+pub fn verify_header_chain(
+    prev: Option<&BlockHeader>,
+    headers: &[BlockHeader],
+) -> Result<(), Error> {
+    let mut prev_hash = prev.map(|h| h.block_hash());
+    for (index, header) in headers.iter().enumerate() {
+        if let Some(prev_hash) = prev_hash {
+            if header.prev_blockhash != prev_hash {
+                return Err(Error::HeaderNotChained(index));
+            }
+        }
+        let hash = header
+            .validate_pow(header.target())
+            .map_err(|_| Error::InvalidProofOfWork(header.block_hash()))?;
+        prev_hash = Some(hash);
+    }
+    Ok(())
+}
+
+/// The merkle root of all the block hashes from genesis up to `height`
+/// (included), it is expected to be hard-coded in the client.
+/// https://electrumx-spesmilo.readthedocs.io/en/latest/protocol-methods.html#cp-height
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub height: usize,
+    pub root: sha256d::Hash,
+}
+
+impl Checkpoint {
+    pub fn new(height: usize, root: sha256d::Hash) -> Self {
+        Self { height, root }
+    }
+
+    /// Check that `header` is at `height` in the chain committed by the checkpoint.
+    pub fn verify(
+        &self,
+        header: &BlockHeader,
+        height: usize,
+        branch: &[sha256d::Hash],
+        root: &sha256d::Hash,
+    ) -> Result<(), Error> {
+        if height > self.height {
+            return Err(Error::InvalidProof);
+        }
+        if *root != self.root {
+            return Err(Error::CheckpointMismatch);
+        }
+        let leaf = sha256d::Hash::from_byte_array(header.block_hash().to_byte_array());
+        if merkle_root_from_branch(leaf, branch, height) == self.root {
+            Ok(())
+        } else {
+            Err(Error::InvalidProof)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use miniscript::bitcoin::{consensus::deserialize, hex::FromHex};
+
+    pub(crate) const HEADERS: [&str; 5] = [
+        "000000206e59d4b0d8d5b9daa4d3ad3093975b0f2a18a6909533350cbfb4b7a04adc6f5f380884ecf7425e488e7f2b249de516e839a5b2d48bcc9b65d45387ce5081c1e8563fe166ffff7f2001000000",
+        "00000020e4a9efb184a77e3b3d75c374823a808f437c5d04fc322f6585c1682ea859a379874002727ca2397cbf8b45bffbd0463c1a8e4f52c23af48b3d8e30c0c4556bd1563fe166ffff7f2001000000",
+        "00000020d02dd6842a2be3611748c75b423d0199f86599a7f565de283ee09ffe3527cf49d2e107eae3f796827fb71fc950ee32f5c45c58704cd0f6de8c5125dfe18d0005573fe166ffff7f2000000000",
+        "0000002007e28823c56f2b29644eaa8060f1e62e622733fbb796a429119963f6318e4d012833a1ec146ca836cbd22f3be596ee73f00134c1edafaeb1178623cf480e554c573fe166ffff7f2006000000",
+        "00000020a7cc866c5522c258d4d08cf78aaf6dec40df9cba90c51b4fb63577dab6000b4805c639b49ecb0ddb0d6e922047310faefc6d69316e137084386a24238d1152ba573fe166ffff7f2000000000",
+    ];
+
+    pub(crate) fn header(raw: &str) -> BlockHeader {
+        deserialize(&Vec::<u8>::from_hex(raw).unwrap()).unwrap()
+    }
+
+    fn parent(left: &sha256d::Hash, right: &sha256d::Hash) -> sha256d::Hash {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(left.as_byte_array());
+        engine.input(right.as_byte_array());
+        sha256d::Hash::from_engine(engine)
+    }
+
+    // Naive merkle tree, returns the root and the branch of the leaf at `index`
+    pub(crate) fn merkle_tree(
+        leaves: &[sha256d::Hash],
+        mut index: usize,
+    ) -> (sha256d::Hash, Vec<sha256d::Hash>) {
+        let mut level = leaves.to_vec();
+        let mut branch = Vec::new();
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(*level.last().unwrap());
+            }
+            branch.push(level[index ^ 1]);
+            level = level.chunks(2).map(|c| parent(&c[0], &c[1])).collect();
+            index >>= 1;
+        }
+        (level[0], branch)
+    }
+
+    fn leaves() -> Vec<sha256d::Hash> {
+        HEADERS
+            .iter()
+            .map(|h| sha256d::Hash::from_byte_array(header(h).block_hash().to_byte_array()))
+            .collect()
+    }
+
+    #[test]
+    fn merkle_root() {
+        let leaves = leaves();
+        for index in 0..leaves.len() {
+            let (root, branch) = merkle_tree(&leaves, index);
+            assert_eq!(merkle_root_from_branch(leaves[index], &branch, index), root);
+        }
+        // single leaf tree
+        assert_eq!(merkle_root_from_branch(leaves[0], &[], 0), leaves[0]);
+    }
+
+    #[test]
+    fn header_chain() {
+        let headers: Vec<_> = HEADERS.iter().map(|h| header(h)).collect();
+        assert!(verify_header_chain(None, &headers).is_ok());
+        assert!(verify_header_chain(Some(&headers[0]), &headers[1..]).is_ok());
+        assert!(verify_header_chain(None, &[]).is_ok());
+
+        // does not link to `prev`
+        assert!(matches!(
+            verify_header_chain(Some(&headers[1]), &headers[1..]),
+            Err(Error::HeaderNotChained(0))
+        ));
+
+        // missing header
+        let mut broken = headers.clone();
+        broken.remove(2);
+        assert!(matches!(
+            verify_header_chain(None, &broken),
+            Err(Error::HeaderNotChained(2))
+        ));
+
+        // insufficient proof of work
+        let mut invalid = headers[0];
+        invalid.bits = miniscript::bitcoin::CompactTarget::from_consensus(0x1d00ffff);
+        assert!(matches!(
+            verify_header_chain(None, &[invalid]),
+            Err(Error::InvalidProofOfWork(_))
+        ));
+    }
+
+    // A header committing to `txids`, returns the header and the branch of
+    // the tx at `pos`
+    pub(crate) fn block(txids: &[Txid], pos: usize) -> (BlockHeader, Vec<TxMerkleNode>) {
+        let leaves: Vec<_> = txids.iter().map(|t| t.to_raw_hash()).collect();
+        let (root, branch) = merkle_tree(&leaves, pos);
+        let mut header = header(HEADERS[0]);
+        header.merkle_root = TxMerkleNode::from_raw_hash(root);
+        let branch = branch
+            .into_iter()
+            .map(TxMerkleNode::from_raw_hash)
+            .collect();
+        (header, branch)
+    }
+
+    pub(crate) fn txids(count: u8) -> Vec<Txid> {
+        (0..count).map(|i| Txid::from_byte_array([i; 32])).collect()
+    }
+
+    #[test]
+    fn tx_inclusion() {
+        let txids = txids(7);
+        for pos in 0..txids.len() {
+            let (header, branch) = block(&txids, pos);
+            assert_eq!(merkle_branch(&txids, pos), Some(branch.clone()));
+            assert!(verify_tx_inclusion(&txids[pos], &branch, pos, &header).is_ok());
+            // wrong position, the last leaf is its own sibling
+            if pos < 6 {
+                assert!(matches!(
+                    verify_tx_inclusion(&txids[pos], &branch, pos ^ 1, &header),
+                    Err(Error::InvalidProof)
+                ));
+            }
+            // wrong txid
+            assert!(matches!(
+                verify_tx_inclusion(&txids[(pos + 1) % 7], &branch, pos, &header),
+                Err(Error::InvalidProof)
+            ));
+        }
+        // block w/ only a coinbase
+        let (header, branch) = block(&txids[..1], 0);
+        assert!(branch.is_empty());
+        assert_eq!(merkle_branch(&txids[..1], 0), Some(Vec::new()));
+        assert_eq!(merkle_branch(&txids[..1], 1), None);
+        assert!(verify_tx_inclusion(&txids[0], &branch, 0, &header).is_ok());
+        // positions out of the tree of the branch
+        assert!(matches!(
+            verify_tx_inclusion(&txids[0], &branch, 12345, &header),
+            Err(Error::InvalidProof)
+        ));
+        let (header, branch) = block(&txids, 2);
+        for pos in [2 + 8, 2 + 64, usize::MAX] {
+            assert!(matches!(
+                verify_tx_inclusion(&txids[2], &branch, pos, &header),
+                Err(Error::InvalidProof)
+            ));
+        }
+    }
+
+    #[test]
+    fn block_txids() {
+        for count in [1, 2, 7, 8] {
+            let txids = txids(count);
+            let (header, _) = block(&txids, 0);
+            assert!(verify_block_txids(&txids, &header).is_ok());
+            // missing tx
+            assert!(matches!(
+                verify_block_txids(&txids[..txids.len() - 1], &header),
+                Err(Error::InvalidProof)
+            ));
+        }
+        let mut txids = txids(4);
+        let (header, _) = block(&txids, 0);
+        txids.swap(1, 2);
+        assert!(verify_block_txids(&txids, &header).is_err());
+
+        // mutated lists w/ the same root
+        let txids = self::txids(3);
+        let (header, _) = block(&txids, 0);
+        let mutated = [txids[0], txids[1], txids[2], txids[2]];
+        assert!(matches!(
+            verify_block_txids(&mutated, &header),
+            Err(Error::InvalidProof)
+        ));
+        let txids = self::txids(6);
+        let (header, _) = block(&txids, 0);
+        let mut mutated = txids.clone();
+        mutated.extend_from_slice(&txids[4..]);
+        assert!(matches!(
+            verify_block_txids(&mutated, &header),
+            Err(Error::InvalidProof)
+        ));
+    }
+
+    #[test]
+    fn checkpoint() {
+        let leaves = leaves();
+        let (root, branch) = merkle_tree(&leaves, 3);
+        let checkpoint = Checkpoint::new(4, root);
+        let header = header(HEADERS[3]);
+
+        assert!(checkpoint.verify(&header, 3, &branch, &root).is_ok());
+        // wrong height
+        assert!(matches!(
+            checkpoint.verify(&header, 2, &branch, &root),
+            Err(Error::InvalidProof)
+        ));
+        // height above the checkpoint
+        assert!(matches!(
+            checkpoint.verify(&header, 5, &branch, &root),
+            Err(Error::InvalidProof)
+        ));
+        // root that does not match the checkpoint
+        assert!(matches!(
+            checkpoint.verify(&header, 3, &branch, &leaves[0]),
+            Err(Error::CheckpointMismatch)
+        ));
+        // wrong header
+        let other = super::tests::header(HEADERS[2]);
+        assert!(matches!(
+            checkpoint.verify(&other, 3, &branch, &root),
+            Err(Error::InvalidProof)
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl ProtocolVersion {
+    pub const V1_0: Self = Self::new(1, 0, 0);
+    pub const V1_1: Self = Self::new(1, 1, 0);
+    pub const V1_2: Self = Self::new(1, 2, 0);
+    pub const V1_3: Self = Self::new(1, 3, 0);
+    pub const V1_4: Self = Self::new(1, 4, 0);
+    pub const V1_4_1: Self = Self::new(1, 4, 1);
+    pub const V1_4_2: Self = Self::new(1, 4, 2);
+    pub const V1_5: Self = Self::new(1, 5, 0);
+    pub const V1_6: Self = Self::new(1, 6, 0);
+
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('.');
+        let mut next = |optional: bool| -> Result<u16, Error> {
+            match parts.next() {
+                Some(p) => p
+                    .parse::<u16>()
+                    .map_err(|_| Error::ProtocolVersion(s.into())),
+                None if optional => Ok(0),
+                None => Err(Error::ProtocolVersion(s.into())),
+            }
+        };
+        let version = Self::new(next(false)?, next(false)?, next(true)?);
+        if parts.next().is_some() {
+            return Err(Error::ProtocolVersion(s.into()));
+        }
+        Ok(version)
+    }
+}
+
+impl Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.patch == 0 {
+            write!(f, "{}.{}", self.major, self.minor)
+        } else {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ProtocolVersion::from_str("1.4").unwrap(),
+            ProtocolVersion::V1_4
+        );
+        assert_eq!(
+            ProtocolVersion::from_str("1.4.2").unwrap(),
+            ProtocolVersion::V1_4_2
+        );
+        assert_eq!(
+            ProtocolVersion::from_str("1.6").unwrap(),
+            ProtocolVersion::V1_6
+        );
+        assert!(ProtocolVersion::from_str("1").is_err());
+        assert!(ProtocolVersion::from_str("1.4.2.1").is_err());
+        assert!(ProtocolVersion::from_str("1.x").is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ProtocolVersion::V1_4.to_string(), "1.4");
+        assert_eq!(ProtocolVersion::V1_4_2.to_string(), "1.4.2");
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(ProtocolVersion::V1_4 < ProtocolVersion::V1_4_2);
+        assert!(ProtocolVersion::V1_4_2 < ProtocolVersion::V1_5);
+        assert!(ProtocolVersion::V1_5 < ProtocolVersion::V1_6);
+    }
+}
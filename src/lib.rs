@@ -1,4 +1,6 @@
 #![allow(dead_code)]
+pub mod chain;
 pub mod client;
 pub mod electrum;
 pub mod raw_client;
+pub mod server;
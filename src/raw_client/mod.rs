@@ -7,6 +7,7 @@ use crate::electrum::{
     self,
     request::Request,
     response::{parse_str_response, Response},
+    types::Id,
 };
 
 use self::{ssl_client::SslClient, tcp_client::TcpClient};
@@ -207,7 +208,12 @@ impl Client {
         }
     }
 
-    pub fn recv(&mut self, index: &HashMap<usize, Request>) -> Result<Vec<Response>, Error> {
+    /// Blocking receive of the next message(s), each element of a batch is
+    /// parsed on its own.
+    pub fn recv(
+        &mut self,
+        index: &HashMap<Id, Request>,
+    ) -> Result<Vec<Result<Response, electrum::Error>>, Error> {
         let raw = self.recv_str()?;
         Ok(parse_str_response(&raw, index)?)
     }
@@ -236,8 +242,8 @@ impl Client {
 
     pub fn try_recv(
         &mut self,
-        index: &HashMap<usize, Request>,
-    ) -> Result<Option<Vec<Response>>, Error> {
+        index: &HashMap<Id, Request>,
+    ) -> Result<Option<Vec<Result<Response, electrum::Error>>>, Error> {
         let raw = self.try_recv_str()?;
         if let Some(rr) = raw {
             Ok(Some(parse_str_response(&rr, index)?))
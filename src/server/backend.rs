@@ -0,0 +1,261 @@
+use miniscript::bitcoin::{
+    block::Header as BlockHeader, hashes::sha256d, FeeRate, OutPoint, Transaction, TxMerkleNode,
+    Txid,
+};
+
+use crate::electrum::{
+    method::Method,
+    params::VersionKind,
+    response::{
+        BalanceResult, BroadcastPackageResult, ErrorResult, FeaturesResult, HistoryResult,
+        MempoolInfo, OutpointStatus, Peer, UtxoResult, VerboseTx,
+    },
+    types::{ScriptHash, StatusHash},
+    verify::merkle_branch,
+    version::ProtocolVersion,
+};
+use serde_json::Value;
+
+/// Lowest protocol version served by default.
+pub const PROTOCOL_MIN: ProtocolVersion = ProtocolVersion::V1_4;
+/// Highest protocol version served by default.
+pub const PROTOCOL_MAX: ProtocolVersion = ProtocolVersion::V1_6;
+
+pub type BackendResult<T> = Result<T, ErrorResult>;
+
+/// Negotiate a protocol version in the `min..=max` range w/ the version(s)
+/// requested by the client in `server.version`.
+pub fn negotiate_version(
+    requested: &VersionKind,
+    min: ProtocolVersion,
+    max: ProtocolVersion,
+) -> BackendResult<ProtocolVersion> {
+    let parse = |v: &str| {
+        v.parse::<ProtocolVersion>()
+            .map_err(|_| ErrorResult::bad_request(format!("invalid protocol version {}", v)))
+    };
+    let (client_min, client_max) = match requested {
+        VersionKind::Single(v) => (parse(v)?, parse(v)?),
+        VersionKind::MinMax(client_min, client_max) => (parse(client_min)?, parse(client_max)?),
+    };
+    let version = client_max.min(max);
+    if version < client_min.max(min) {
+        return Err(ErrorResult::bad_request(format!(
+            "unsupported protocol version {}",
+            client_max
+        )));
+    }
+    Ok(version)
+}
+
+/// The data source of a [`super::Server`], there is one method per Electrum
+/// method, methods not implemented by the backend reply w/ an error.
+///
+/// Subscriptions are managed by the server, it calls [`ElectrumBackend::tip()`],
+/// [`ElectrumBackend::status()`] & [`ElectrumBackend::outpoint_status()`] on
+/// subscription & when notified w/ the matching [`super::ServerHandle`] method.
+pub trait ElectrumBackend: Send + Sync + 'static {
+    /// Returns the server software name & the negotiated protocol version.
+    fn version(
+        &self,
+        _client_name: &str,
+        protocol: &VersionKind,
+    ) -> BackendResult<(String, ProtocolVersion)> {
+        let version = negotiate_version(protocol, PROTOCOL_MIN, PROTOCOL_MAX)?;
+        let name = format!("simple_electrum_client {}", env!("CARGO_PKG_VERSION"));
+        Ok((name, version))
+    }
+
+    fn banner(&self) -> BackendResult<String> {
+        Err(ErrorResult::unsupported(&Method::Banner))
+    }
+
+    fn donation_address(&self) -> BackendResult<Option<String>> {
+        Err(ErrorResult::unsupported(&Method::Donation))
+    }
+
+    fn features(&self) -> BackendResult<FeaturesResult> {
+        Err(ErrorResult::unsupported(&Method::Features))
+    }
+
+    fn peers(&self) -> BackendResult<Vec<Peer>> {
+        Err(ErrorResult::unsupported(&Method::ListPeers))
+    }
+
+    fn add_peer(&self, _features: &FeaturesResult) -> BackendResult<bool> {
+        Err(ErrorResult::unsupported(&Method::AddPeer))
+    }
+
+    /// Height & header of the chain tip.
+    fn tip(&self) -> BackendResult<(usize, BlockHeader)> {
+        Err(ErrorResult::unsupported(&Method::HeadersSubscribe))
+    }
+
+    fn block_header(&self, _height: usize) -> BackendResult<BlockHeader> {
+        Err(ErrorResult::unsupported(&Method::BlockHeader))
+    }
+
+    /// Returns up to `count` headers starting at `start`, `count` is already
+    /// capped to [`super::MAX_HEADERS`].
+    fn block_headers(&self, start: usize, count: usize) -> BackendResult<Vec<BlockHeader>> {
+        let tip = self.tip()?.0;
+        (start..start.saturating_add(count).min(tip + 1))
+            .map(|height| self.block_header(height))
+            .collect()
+    }
+
+    /// Returns the merkle branch & root of the header at `height` in the tree
+    /// of the block hashes up to `cp_height`.
+    fn header_proof(
+        &self,
+        _height: usize,
+        _cp_height: usize,
+    ) -> BackendResult<(Vec<sha256d::Hash>, sha256d::Hash)> {
+        Err(ErrorResult::bad_request(
+            "checkpoint proofs are not supported",
+        ))
+    }
+
+    /// The txids of the block at `height` in block order, used by the default
+    /// implementations of [`ElectrumBackend::merkle()`] &
+    /// [`ElectrumBackend::tx_from_pos()`].
+    fn block_txids(&self, _height: usize) -> BackendResult<Vec<Txid>> {
+        Err(ErrorResult::unsupported(&Method::TransactionFromPosition))
+    }
+
+    fn estimate_fee(&self, _block_target: u16) -> BackendResult<Option<FeeRate>> {
+        Err(ErrorResult::unsupported(&Method::EstimateFee))
+    }
+
+    fn relay_fee(&self) -> BackendResult<Option<FeeRate>> {
+        Err(ErrorResult::unsupported(&Method::RelayFee))
+    }
+
+    /// Fee rates in sat/vB & sizes in vB.
+    fn fee_histogram(&self) -> BackendResult<Vec<(f64, u64)>> {
+        Err(ErrorResult::unsupported(&Method::FeeHistogram))
+    }
+
+    fn mempool_info(&self) -> BackendResult<MempoolInfo> {
+        Err(ErrorResult::unsupported(&Method::MempoolGetInfo))
+    }
+
+    fn balance(&self, _script_hash: &ScriptHash) -> BackendResult<BalanceResult> {
+        Err(ErrorResult::unsupported(&Method::ScriptHashGetBalance))
+    }
+
+    fn history(&self, _script_hash: &ScriptHash) -> BackendResult<Vec<HistoryResult>> {
+        Err(ErrorResult::unsupported(&Method::ScriptHashGetHistory))
+    }
+
+    /// Unconfirmed transactions of a script hash, taken from its history by
+    /// default.
+    fn mempool(&self, script_hash: &ScriptHash) -> BackendResult<Vec<HistoryResult>> {
+        Ok(self
+            .history(script_hash)?
+            .into_iter()
+            .filter(|tx| !tx.is_confirmed())
+            .collect())
+    }
+
+    fn list_unspent(&self, _script_hash: &ScriptHash) -> BackendResult<Vec<UtxoResult>> {
+        Err(ErrorResult::unsupported(&Method::ScriptHashListUnspent))
+    }
+
+    /// Status of a script hash, computed from its history by default.
+    fn status(&self, script_hash: &ScriptHash) -> BackendResult<Option<StatusHash>> {
+        Ok(StatusHash::from_history(&self.history(script_hash)?))
+    }
+
+    fn outpoint_status(&self, _outpoint: &OutPoint) -> BackendResult<OutpointStatus> {
+        Err(ErrorResult::unsupported(&Method::OutpointSubscribe))
+    }
+
+    fn transaction(&self, _txid: &Txid) -> BackendResult<Transaction> {
+        Err(ErrorResult::unsupported(&Method::TransactionGet))
+    }
+
+    fn verbose_transaction(&self, _txid: &Txid) -> BackendResult<VerboseTx> {
+        Err(ErrorResult::bad_request(
+            "verbose transactions are not supported",
+        ))
+    }
+
+    /// Returns the merkle branch & the position of `txid` in the block at `height`.
+    fn merkle(&self, txid: &Txid, height: usize) -> BackendResult<(Vec<TxMerkleNode>, usize)> {
+        let txids = self.block_txids(height)?;
+        let pos = txids.iter().position(|t| t == txid).ok_or_else(|| {
+            ErrorResult::bad_request(format!("{} is not in block {}", txid, height))
+        })?;
+        let branch = merkle_branch(&txids, pos).expect("in range");
+        Ok((branch, pos))
+    }
+
+    /// Returns the txid at `tx_pos` in the block at `height` & its merkle branch.
+    fn tx_from_pos(
+        &self,
+        height: usize,
+        tx_pos: usize,
+    ) -> BackendResult<(Txid, Vec<TxMerkleNode>)> {
+        let txids = self.block_txids(height)?;
+        let branch = merkle_branch(&txids, tx_pos).ok_or_else(|| {
+            ErrorResult::bad_request(format!("no tx at position {} in block {}", tx_pos, height))
+        })?;
+        Ok((txids[tx_pos], branch))
+    }
+
+    fn broadcast(&self, _tx: &Transaction) -> BackendResult<Txid> {
+        Err(ErrorResult::unsupported(&Method::TransactionBroadcast))
+    }
+
+    fn broadcast_package(&self, _txs: &[Transaction]) -> BackendResult<BroadcastPackageResult> {
+        Err(ErrorResult::unsupported(
+            &Method::TransactionBroadcastPackage,
+        ))
+    }
+
+    /// Names of the methods handled by [`ElectrumBackend::custom()`], the
+    /// requests to other unknown methods are rejected.
+    fn custom_methods(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Handle a method that is not part of the Electrum protocol (server
+    /// extension), `params` are passed as received.
+    fn custom(&self, method: &str, _params: &Value) -> BackendResult<Value> {
+        Err(ErrorResult::method_not_found(method))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single(v: &str) -> VersionKind {
+        VersionKind::Single(v.into())
+    }
+
+    fn range(min: &str, max: &str) -> VersionKind {
+        VersionKind::MinMax(min.into(), max.into())
+    }
+
+    #[test]
+    fn negotiate() {
+        let (min, max) = (ProtocolVersion::V1_4, ProtocolVersion::V1_6);
+        assert_eq!(
+            negotiate_version(&single("1.4"), min, max).unwrap(),
+            ProtocolVersion::V1_4
+        );
+        assert_eq!(
+            negotiate_version(&range("1.4", "2.0"), min, max).unwrap(),
+            ProtocolVersion::V1_6
+        );
+        assert_eq!(
+            negotiate_version(&range("1.1", "1.4.2"), min, max).unwrap(),
+            ProtocolVersion::V1_4_2
+        );
+        assert!(negotiate_version(&single("1.2"), min, max).is_err());
+        assert!(negotiate_version(&range("1.7", "1.8"), min, max).is_err());
+        assert!(negotiate_version(&single("toto"), min, max).is_err());
+    }
+}
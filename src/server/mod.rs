@@ -0,0 +1,371 @@
+pub mod backend;
+pub(crate) mod session;
+
+use std::{
+    io,
+    net::{self, SocketAddr, ToSocketAddrs},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use miniscript::bitcoin::{consensus::encode::serialize_hex, OutPoint};
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+use crate::electrum::{
+    method::Method,
+    response::{BatchHeaderNotif, Header, OutpointNotification, Response, SHNotification},
+    types::ScriptHash,
+};
+
+pub use self::backend::{BackendResult, ElectrumBackend};
+use self::session::{Session, Stream};
+
+/// Max number of headers returned by `blockchain.block.headers`.
+pub const MAX_HEADERS: usize = 2016;
+/// Max size of a request line, the connection is closed if exceeded.
+pub const MAX_LINE_SIZE: usize = 4 * 1024 * 1024;
+/// Default max number of simultaneous connections, see
+/// [`Server::max_connections()`].
+pub const MAX_CONNECTIONS: usize = 256;
+/// Interval at which the listener & the connections check if the server
+/// has been stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const READ_BUFFER_SIZE: usize = 4096;
+/// Timeout of the SSL handshake & of the writes to a client.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Ssl(openssl::error::ErrorStack),
+    SslHandshake(String),
+    Mutex,
+    Serialize,
+    Closed,
+    LineTooLong,
+}
+
+struct Shared<B> {
+    backend: B,
+    sessions: Mutex<Vec<Arc<Session>>>,
+    stop: AtomicBool,
+    // connection threads running
+    connections: AtomicUsize,
+    max_connections: usize,
+}
+
+impl<B> Shared<B> {
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    fn sessions(&self) -> Vec<Arc<Session>> {
+        self.sessions.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+}
+
+/// An Electrum server serving the data of an [`ElectrumBackend`] over TCP or SSL.
+pub struct Server<B> {
+    backend: B,
+    acceptor: Option<SslAcceptor>,
+    max_connections: usize,
+}
+
+impl<B: ElectrumBackend> Server<B> {
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            acceptor: None,
+            max_connections: MAX_CONNECTIONS,
+        }
+    }
+
+    /// Set the max number of simultaneous connections, the connections
+    /// exceeding it are closed right away.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    pub fn ssl(mut self, acceptor: SslAcceptor) -> Self {
+        self.acceptor = Some(acceptor);
+        self
+    }
+
+    /// Serve over SSL w/ the PEM encoded certificate (chain) & private key files.
+    pub fn ssl_from_pem(self, cert: &Path, key: &Path) -> Result<Self, Error> {
+        let mut acceptor =
+            SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).map_err(Error::Ssl)?;
+        acceptor
+            .set_certificate_chain_file(cert)
+            .map_err(Error::Ssl)?;
+        acceptor
+            .set_private_key_file(key, SslFiletype::PEM)
+            .map_err(Error::Ssl)?;
+        acceptor.check_private_key().map_err(Error::Ssl)?;
+        Ok(self.ssl(acceptor.build()))
+    }
+
+    /// Start listening on `addr`, connections are served in their own thread
+    /// until the returned handle is stopped or dropped.
+    pub fn bind<A: ToSocketAddrs>(self, addr: A) -> Result<ServerHandle<B>, Error> {
+        let listener = net::TcpListener::bind(addr).map_err(Error::Io)?;
+        let addr = listener.local_addr().map_err(Error::Io)?;
+        listener.set_nonblocking(true).map_err(Error::Io)?;
+        let shared = Arc::new(Shared {
+            backend: self.backend,
+            sessions: Mutex::new(Vec::new()),
+            stop: AtomicBool::new(false),
+            connections: AtomicUsize::new(0),
+            max_connections: self.max_connections,
+        });
+        let acceptor = self.acceptor.map(Arc::new);
+        let s = shared.clone();
+        let thread = thread::spawn(move || listen(s, listener, acceptor));
+        Ok(ServerHandle {
+            addr,
+            shared,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn listen<B: ElectrumBackend>(
+    shared: Arc<Shared<B>>,
+    listener: net::TcpListener,
+    acceptor: Option<Arc<SslAcceptor>>,
+) {
+    while !shared.stopped() {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                log::debug!("Connection from {}", peer);
+                if shared.connections.fetch_add(1, Ordering::SeqCst) >= shared.max_connections {
+                    shared.connections.fetch_sub(1, Ordering::SeqCst);
+                    log::warn!("Too many connections, close {}", peer);
+                    continue;
+                }
+                let shared = shared.clone();
+                let acceptor = acceptor.clone();
+                thread::spawn(move || {
+                    let _guard = ConnectionGuard(&shared);
+                    if let Err(e) = serve(&shared, stream, acceptor) {
+                        log::error!("Connection with {} failed: {:?}", peer, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                log::error!("Fail to accept connection: {:?}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+/// Release the connection slot of a connection thread, even if it panics.
+struct ConnectionGuard<'a, B>(&'a Shared<B>);
+
+impl<B> Drop for ConnectionGuard<'_, B> {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Close & unregister a session, even if a backend method panics, so it does
+/// not keep receiving the broadcasts.
+struct SessionGuard<'a, B> {
+    shared: &'a Shared<B>,
+    session: Arc<Session>,
+}
+
+impl<B> Drop for SessionGuard<'_, B> {
+    fn drop(&mut self) {
+        self.session.close();
+        if let Ok(mut sessions) = self.shared.sessions.lock() {
+            sessions.retain(|s| !Arc::ptr_eq(s, &self.session));
+        }
+    }
+}
+
+fn serve<B: ElectrumBackend>(
+    shared: &Shared<B>,
+    stream: net::TcpStream,
+    acceptor: Option<Arc<SslAcceptor>>,
+) -> Result<(), Error> {
+    stream.set_nonblocking(false).map_err(Error::Io)?;
+    // NOTE: a client stalling the handshake or not reading its replies
+    // must not hold the connection thread forever
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .map_err(Error::Io)?;
+    stream
+        .set_write_timeout(Some(IO_TIMEOUT))
+        .map_err(Error::Io)?;
+    let stream = match acceptor {
+        Some(acceptor) => Stream::Ssl(Box::new(
+            acceptor
+                .accept(stream)
+                .map_err(|e| Error::SslHandshake(e.to_string()))?,
+        )),
+        None => Stream::Tcp(stream),
+    };
+    // NOTE: reads time out so the connection can be polled for notifications
+    // & checked for shutdown
+    stream
+        .tcp()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(Error::Io)?;
+    let session = Arc::new(Session::new(Some(stream)));
+    shared
+        .sessions
+        .lock()
+        .map_err(|_| Error::Mutex)?
+        .push(session.clone());
+    let guard = SessionGuard { shared, session };
+
+    run(shared, &guard.session)
+}
+
+fn run<B: ElectrumBackend>(shared: &Shared<B>, session: &Session) -> Result<(), Error> {
+    let mut pending = Vec::new();
+    // NOTE: `pending[..scanned]` is known to contain no end of line
+    let mut scanned = 0;
+    let mut buf = [0u8; READ_BUFFER_SIZE];
+    while !shared.stopped() {
+        match session.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => pending.extend_from_slice(&buf[..n]),
+            Err(Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+        let mut start = 0;
+        while let Some(eol) = pending[scanned..].iter().position(|b| *b == b'\n') {
+            let end = scanned + eol + 1;
+            if let Some(reply) = session.handle_line(&shared.backend, &pending[start..end]) {
+                session.send(&reply)?;
+            }
+            start = end;
+            scanned = end;
+        }
+        pending.drain(..start);
+        scanned = pending.len();
+        if pending.len() > MAX_LINE_SIZE {
+            return Err(Error::LineTooLong);
+        }
+    }
+    Ok(())
+}
+
+/// Handle of a running [`Server`], used to push notifications to the
+/// subscribed clients.
+pub struct ServerHandle<B: ElectrumBackend> {
+    addr: SocketAddr,
+    shared: Arc<Shared<B>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<B: ElectrumBackend> ServerHandle<B> {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.shared.backend
+    }
+
+    /// Number of clients connected.
+    pub fn connections(&self) -> usize {
+        self.shared.sessions().len()
+    }
+
+    fn broadcast(&self, notification: &Response, filter: impl Fn(&Session) -> bool) {
+        for session in self.shared.sessions() {
+            if filter(&session) {
+                if let Err(e) = session.notify(notification) {
+                    log::error!("Fail to send notification: {:?}", e);
+                }
+            }
+        }
+    }
+
+    /// Notify the clients subscribed to headers of the current tip of the
+    /// backend.
+    pub fn notify_tip(&self) -> BackendResult<()> {
+        let (height, header) = self.shared.backend.tip()?;
+        let notification = Response::BatchHeaderNotif(BatchHeaderNotif {
+            method: Method::HeadersSubscribe,
+            headers: vec![Header {
+                height,
+                raw_header: serialize_hex(&header),
+            }],
+        });
+        self.broadcast(&notification, |s| s.subscriptions().headers);
+        Ok(())
+    }
+
+    /// Notify the clients subscribed to `script_hash` if its status changed
+    /// since the last status they received.
+    pub fn notify_script_hash(&self, script_hash: &ScriptHash) -> BackendResult<()> {
+        let status = self.shared.backend.status(script_hash)?;
+        let notification = Response::SHNotification(SHNotification {
+            method: Method::ScriptHashSubscribe,
+            status: (*script_hash, status.map(|s| s.to_string())),
+        });
+        self.broadcast(&notification, |s| {
+            match s.subscriptions().script_hashes.get_mut(script_hash) {
+                Some(last) if *last != status => {
+                    *last = status;
+                    true
+                }
+                _ => false,
+            }
+        });
+        Ok(())
+    }
+
+    /// Notify the clients subscribed to `outpoint` of its current status.
+    pub fn notify_outpoint(&self, outpoint: &OutPoint) -> BackendResult<()> {
+        let status = self.shared.backend.outpoint_status(outpoint)?;
+        let notification = Response::OutpointNotification(OutpointNotification {
+            method: Method::OutpointSubscribe,
+            status: ((outpoint.txid, outpoint.vout), status),
+        });
+        self.broadcast(&notification, |s| {
+            s.subscriptions().outpoints.contains(outpoint)
+        });
+        Ok(())
+    }
+
+    /// Stop listening & close all the connections.
+    pub fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let sessions = match self.shared.sessions.lock() {
+            Ok(mut sessions) => sessions.drain(..).collect(),
+            Err(_) => Vec::new(),
+        };
+        for session in sessions {
+            session.close();
+        }
+    }
+}
+
+impl<B: ElectrumBackend> Drop for ServerHandle<B> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
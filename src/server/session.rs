@@ -0,0 +1,637 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read, Write},
+    net,
+    sync::Mutex,
+};
+
+use miniscript::bitcoin::{
+    consensus::encode::{deserialize_hex, serialize_hex},
+    OutPoint, Transaction,
+};
+use openssl::ssl;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{
+    backend::{BackendResult, ElectrumBackend},
+    Error, MAX_HEADERS,
+};
+use crate::electrum::{
+    method::Method,
+    params::{Params, VersionKind},
+    request::Request,
+    response::*,
+    types::{Id, ScriptHash, StatusHash},
+};
+
+pub(crate) enum Stream {
+    Tcp(net::TcpStream),
+    Ssl(Box<ssl::SslStream<net::TcpStream>>),
+}
+
+impl Stream {
+    pub(crate) fn tcp(&self) -> &net::TcpStream {
+        match self {
+            Stream::Tcp(s) => s,
+            Stream::Ssl(s) => s.get_ref(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Ssl(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Ssl(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Ssl(s) => s.flush(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Subscriptions {
+    pub headers: bool,
+    // last status sent to the client
+    pub script_hashes: HashMap<ScriptHash, Option<StatusHash>>,
+    pub outpoints: HashSet<OutPoint>,
+}
+
+/// The state of a client connection.
+pub(crate) struct Session {
+    // NOTE: the connection thread only hold the lock while polling the
+    // stream, so notifications can be sent in between
+    stream: Mutex<Option<Stream>>,
+    pub(crate) subscriptions: Mutex<Subscriptions>,
+}
+
+fn error_value(id: Option<Id>, method: Option<Method>, error: ErrorResult) -> Value {
+    response_value(&Response::Error(ErrorResponse { id, error, method }))
+}
+
+fn response_value(response: &Response) -> Value {
+    serde_json::to_value(response).unwrap_or_else(|e| {
+        let error = ErrorResult::internal(e.to_string());
+        json!({"jsonrpc": "2.0", "id": response.id(), "error": error})
+    })
+}
+
+fn parse_tx(raw: &str) -> BackendResult<Transaction> {
+    deserialize_hex(raw)
+        .map_err(|_| ErrorResult::bad_request(format!("invalid transaction {}", raw)))
+}
+
+impl Session {
+    pub(crate) fn new(stream: Option<Stream>) -> Self {
+        Self {
+            stream: Mutex::new(stream),
+            subscriptions: Mutex::new(Subscriptions::default()),
+        }
+    }
+
+    /// Read from the stream, `WouldBlock` & `TimedOut` errors are returned
+    /// when no data is received before the read timeout.
+    pub(crate) fn read(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut stream = self.stream.lock().map_err(|_| Error::Mutex)?;
+        match stream.as_mut() {
+            Some(s) => s.read(buf).map_err(Error::Io),
+            None => Ok(0),
+        }
+    }
+
+    pub(crate) fn send(&self, message: &Value) -> Result<(), Error> {
+        let mut stream = self.stream.lock().map_err(|_| Error::Mutex)?;
+        let stream = stream.as_mut().ok_or(Error::Closed)?;
+        let raw = serde_json::to_string(message).map_err(|_| Error::Serialize)?;
+        stream.write_all(raw.as_bytes()).map_err(Error::Io)?;
+        // add a \n char for EOL
+        stream.write_all(&[10]).map_err(Error::Io)?;
+        stream.flush().map_err(Error::Io)
+    }
+
+    pub(crate) fn notify(&self, notification: &Response) -> Result<(), Error> {
+        self.send(&response_value(notification))
+    }
+
+    pub(crate) fn close(&self) {
+        if let Ok(mut stream) = self.stream.lock() {
+            if let Some(s) = stream.take() {
+                let _ = s.tcp().shutdown(net::Shutdown::Both);
+            }
+        }
+    }
+
+    /// Handle a line received from the client, returns the message to reply
+    /// if any.
+    pub(crate) fn handle_line<B: ElectrumBackend>(
+        &self,
+        backend: &B,
+        line: &[u8],
+    ) -> Option<Value> {
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return None;
+        }
+        match serde_json::from_slice::<Value>(line) {
+            Err(_) => Some(error_value(None, None, ErrorResult::parse_error())),
+            Ok(Value::Array(batch)) if batch.is_empty() => {
+                Some(error_value(None, None, ErrorResult::invalid_request()))
+            }
+            Ok(Value::Array(batch)) => {
+                let replies: Vec<_> = batch
+                    .into_iter()
+                    .filter_map(|request| self.handle_value(backend, request))
+                    .collect();
+                // NOTE: a batch of notifications gets no reply at all
+                (!replies.is_empty()).then_some(Value::Array(replies))
+            }
+            Ok(request) => self.handle_value(backend, request),
+        }
+    }
+
+    fn handle_value<B: ElectrumBackend>(&self, backend: &B, value: Value) -> Option<Value> {
+        let id = value.get("id").and_then(|id| Id::deserialize(id).ok());
+        let method = match value.get("method") {
+            Some(Value::String(m)) => m.clone(),
+            _ => return Some(error_value(id, None, ErrorResult::invalid_request())),
+        };
+        // NOTE: a request w/o id is a notification & must not be replied
+        value.get("id")?;
+        // NOTE: a misspelled method is custom too, only the custom methods
+        // registered by the backend are served
+        let method = Method::from_name(&method);
+        if let Method::Custom(name) = &method {
+            if !backend.custom_methods().contains(&name.as_str()) {
+                return Some(error_value(id, None, ErrorResult::method_not_found(name)));
+            }
+        }
+        if id.is_none() {
+            return Some(error_value(None, None, ErrorResult::invalid_request()));
+        }
+        let request: Request = match serde_json::from_value(value) {
+            Ok(r) => r,
+            Err(_) => return Some(error_value(id, None, ErrorResult::invalid_params(&method))),
+        };
+        Some(match self.dispatch(backend, &request) {
+            Ok(response) => response_value(&response),
+            Err(e) => error_value(id, None, e),
+        })
+    }
+
+    pub(crate) fn dispatch<B: ElectrumBackend>(
+        &self,
+        backend: &B,
+        request: &Request,
+    ) -> BackendResult<Response> {
+        let id = request.id.clone();
+        let invalid = || ErrorResult::invalid_params(&request.method);
+        Ok(match (&request.method, request.params()) {
+            (Method::Ping, _) => Response::Ping(PingResponse { id, result: None }),
+            (Method::Version, Params::Version((client_name, protocol))) => {
+                let (name, version) = backend.version(client_name, protocol)?;
+                let version = ResultVersion((name, VersionKind::Single(version.to_string())));
+                Response::Version(VersionResponse { id, version })
+            }
+            (Method::Banner, _) => Response::Banner(BannerResponse {
+                id,
+                result: backend.banner()?,
+            }),
+            (Method::Donation, _) => Response::Donation(DonationResponse {
+                id,
+                address: backend.donation_address()?,
+            }),
+            (Method::Features, _) => Response::Features(FeaturesResponse {
+                id,
+                features: backend.features()?,
+            }),
+            (Method::ListPeers, _) => Response::ListPeers(ListPeersResponse {
+                id,
+                peers: backend.peers()?,
+            }),
+            (Method::AddPeer, Params::AddPeer((features,))) => Response::AddPeer(AddPeerResponse {
+                id,
+                result: backend.add_peer(features)?,
+            }),
+            (Method::HeadersSubscribe, _) => {
+                let (height, header) = backend.tip()?;
+                self.subscriptions().headers = true;
+                let header = Header {
+                    height,
+                    raw_header: serialize_hex(&header),
+                };
+                Response::HeaderNotif(HeaderNotification::Single(SingleHeaderNotif { id, header }))
+            }
+            (Method::BlockHeader, params) => {
+                let height = match params {
+                    Params::BlockHeader((height,))
+                    | Params::BlockHeaderWithCheckpoint((height, _)) => *height,
+                    _ => return Err(invalid()),
+                };
+                let raw_header = serialize_hex(&backend.block_header(height)?);
+                match params.checkpoint() {
+                    Some(cp_height) if height <= cp_height => {
+                        let (branch, root) = backend.header_proof(height, cp_height)?;
+                        let header = HeaderWithProof {
+                            branch: branch.iter().map(|h| h.to_string()).collect(),
+                            raw_header,
+                            root: root.to_string(),
+                        };
+                        Response::HeaderWithProof(HeaderWithProofResponse { id, header })
+                    }
+                    Some(_) => return Err(invalid()),
+                    None => Response::Header(HeaderResponse { id, raw_header }),
+                }
+            }
+            (Method::BlockHeaders, params) => {
+                let (start, count) = match params {
+                    Params::BlockHeaders((start, count))
+                    | Params::BlockHeadersWithCheckpoint((start, count, _)) => {
+                        (*start, (*count).min(MAX_HEADERS))
+                    }
+                    _ => return Err(invalid()),
+                };
+                let headers = backend.block_headers(start, count)?;
+                let count = headers.len();
+                let raw_headers: String = headers.iter().map(serialize_hex).collect();
+                let last = count.checked_sub(1).and_then(|n| start.checked_add(n));
+                match (params.checkpoint(), last) {
+                    (Some(cp_height), Some(last)) if last <= cp_height => {
+                        // NOTE: the proof is the one of the last header returned
+                        let (branch, root) = backend.header_proof(last, cp_height)?;
+                        let headers = HeadersWithProof {
+                            count,
+                            raw_headers,
+                            max: MAX_HEADERS,
+                            branch: branch.iter().map(|h| h.to_string()).collect(),
+                            root: root.to_string(),
+                        };
+                        Response::HeadersWithProof(HeadersWithProofResponse { id, headers })
+                    }
+                    (Some(_), _) => return Err(invalid()),
+                    (None, _) => Response::Headers(HeadersResponse {
+                        id,
+                        headers: Headers {
+                            count,
+                            raw_headers,
+                            max: MAX_HEADERS,
+                        },
+                    }),
+                }
+            }
+            (Method::EstimateFee, Params::EstimateFee((target,))) => {
+                Response::EstimateFee(EstimateFeeResponse {
+                    id,
+                    fee: backend.estimate_fee(*target)?.into(),
+                })
+            }
+            (Method::RelayFee, _) => Response::RelayFee(RelayFeeResponse {
+                id,
+                fee: backend.relay_fee()?.into(),
+            }),
+            (Method::FeeHistogram, _) => Response::FeeHistogram(FeeHistogramResponse {
+                id,
+                histogram: backend.fee_histogram()?,
+            }),
+            (Method::MempoolGetInfo, _) => Response::MempoolInfo(MempoolInfoResponse {
+                id,
+                info: backend.mempool_info()?,
+            }),
+            (Method::ScriptHashGetBalance, Params::ScriptHashGetBalance((sh,))) => {
+                Response::SHGetBalance(SHGetBalanceResponse {
+                    id,
+                    balance: backend.balance(sh)?,
+                })
+            }
+            (Method::ScriptHashGetHistory, Params::ScriptHashGetHistory((sh,))) => {
+                Response::SHGetHistory(SHGetHistoryResponse {
+                    id,
+                    history: backend.history(sh)?,
+                })
+            }
+            (Method::ScriptHashGetMempool, Params::ScriptHashGetMempool((sh,))) => {
+                Response::SHGetMempool(SHGetMempoolResponse {
+                    id,
+                    mempool: backend.mempool(sh)?,
+                })
+            }
+            (Method::ScriptHashListUnspent, Params::ScriptHashListUnspent((sh,))) => {
+                Response::SHListUnspent(SHListUnspentResponse {
+                    id,
+                    unspent: backend.list_unspent(sh)?,
+                })
+            }
+            (Method::ScriptHashSubscribe, Params::ScriptHashSubscribe((sh,))) => {
+                let status = backend.status(sh)?;
+                self.subscriptions().script_hashes.insert(*sh, status);
+                Response::SHSubscribe(SHSubscribeResponse {
+                    id,
+                    result: status.map(|s| s.to_string()),
+                })
+            }
+            (Method::ScriptHashUnsubscribe, Params::ScriptHashUnsubscribe((sh,))) => {
+                let result = self.subscriptions().script_hashes.remove(sh).is_some();
+                Response::SHUnsubscribe(SHUnsubscribeResponse { id, result })
+            }
+            (Method::OutpointSubscribe, Params::OutpointSubscribe((txid, vout))) => {
+                let outpoint = OutPoint::new(*txid, *vout);
+                let status = backend.outpoint_status(&outpoint)?;
+                self.subscriptions().outpoints.insert(outpoint);
+                Response::OutpointSubscribe(OutpointSubscribeResponse { id, status })
+            }
+            (Method::OutpointUnsubscribe, Params::OutpointUnsubscribe((txid, vout))) => {
+                let outpoint = OutPoint::new(*txid, *vout);
+                let result = self.subscriptions().outpoints.remove(&outpoint);
+                Response::OutpointUnsubscribe(OutpointUnsubscribeResponse { id, result })
+            }
+            (Method::TransactionGet, Params::TransactionGet(args)) => {
+                let (txid, verbose) = args.into();
+                let result = if verbose {
+                    TxGetResult::Verbose(Box::new(backend.verbose_transaction(&txid)?))
+                } else {
+                    TxGetResult::Raw(serialize_hex(&backend.transaction(&txid)?))
+                };
+                Response::TxGet(TxGetResponse { id, result })
+            }
+            (Method::TransactionGetMerkle, Params::TransactionGetMerkle((txid, height))) => {
+                let (branch, pos) = backend.merkle(txid, *height)?;
+                Response::TxGetMerkle(TxGetMerkleResponse {
+                    id,
+                    result: GetMerkleResult::new(branch, *height, pos),
+                })
+            }
+            (
+                Method::TransactionFromPosition,
+                Params::TransactionFromPosition((height, tx_pos, merkle)),
+            ) => {
+                let (txid, branch) = backend.tx_from_pos(*height, *tx_pos)?;
+                let tx = match merkle {
+                    true => TxfromPosResult::WithMerkle {
+                        txid,
+                        merkle: branch,
+                    },
+                    false => TxfromPosResult::Simple(txid),
+                };
+                Response::TxFromposition(TxFromPositionResponse { id, tx })
+            }
+            (Method::TransactionBroadcast, Params::TransactionBroadcast((raw,))) => {
+                let tx = parse_tx(raw)?;
+                Response::TxBroadcast(TxBroadcastResponse {
+                    id,
+                    txid: backend.broadcast(&tx)?,
+                })
+            }
+            (
+                Method::TransactionBroadcastPackage,
+                Params::TransactionBroadcastPackage((raw_txs,)),
+            ) => {
+                let txs = raw_txs
+                    .iter()
+                    .map(|raw| parse_tx(raw))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Response::TxBroadcastPackage(TxBroadcastPackageResponse {
+                    id,
+                    result: backend.broadcast_package(&txs)?,
+                })
+            }
+            (Method::Custom(method), Params::Custom(params)) => Response::Custom(CustomResponse {
+                id,
+                result: backend.custom(method, params)?,
+                method: method.clone(),
+            }),
+            _ => return Err(invalid()),
+        })
+    }
+
+    pub(crate) fn subscriptions(&self) -> std::sync::MutexGuard<'_, Subscriptions> {
+        // NOTE: the lock is never held across a panic point
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use miniscript::bitcoin::{
+        blockdata::constants::genesis_block, hashes::Hash, Amount, Network, Txid,
+    };
+
+    use super::*;
+
+    struct Backend;
+
+    impl ElectrumBackend for Backend {
+        fn banner(&self) -> BackendResult<String> {
+            Ok("hello".into())
+        }
+
+        fn tip(&self) -> BackendResult<(usize, miniscript::bitcoin::block::Header)> {
+            Ok((0, genesis_block(Network::Regtest).header))
+        }
+
+        fn block_header(&self, height: usize) -> BackendResult<miniscript::bitcoin::block::Header> {
+            match height {
+                0 => Ok(genesis_block(Network::Regtest).header),
+                _ => Err(ErrorResult::bad_request("height out of range")),
+            }
+        }
+
+        fn history(&self, _: &ScriptHash) -> BackendResult<Vec<HistoryResult>> {
+            Ok(vec![HistoryResult {
+                height: TxHeight::Confirmed(1),
+                txid: Txid::all_zeros(),
+                fee: None,
+            }])
+        }
+
+        fn list_unspent(&self, _: &ScriptHash) -> BackendResult<Vec<UtxoResult>> {
+            Ok(vec![UtxoResult {
+                height: TxHeight::Mempool,
+                txid: Txid::all_zeros(),
+                vout: 1,
+                value: Amount::from_sat(1000),
+            }])
+        }
+
+        fn custom_methods(&self) -> &[&str] {
+            &["server.echo"]
+        }
+
+        fn custom(&self, method: &str, params: &Value) -> BackendResult<Value> {
+            match method {
+                "server.echo" => Ok(params.clone()),
+                m => Err(ErrorResult::method_not_found(m)),
+            }
+        }
+    }
+
+    fn handle(session: &Session, line: &str) -> Value {
+        session.handle_line(&Backend, line.as_bytes()).unwrap()
+    }
+
+    fn error_code(value: &Value) -> RpcErrorCode {
+        value["error"]["code"].as_i64().unwrap().into()
+    }
+
+    #[test]
+    fn handle_line() {
+        let session = Session::new(None);
+        assert!(session.handle_line(&Backend, b"  \r\n").is_none());
+
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":3,"method":"server.banner","params":[]}"#,
+        );
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"id":3,"jsonrpc":"2.0","result":"hello"}"#
+        );
+
+        let reply = handle(&session, "not json");
+        assert_eq!(error_code(&reply), RpcErrorCode::ParseError);
+        assert_eq!(reply["id"], Value::Null);
+
+        let reply = handle(&session, r#"{"jsonrpc":"2.0","id":4,"method":"toto"}"#);
+        assert_eq!(error_code(&reply), RpcErrorCode::MethodNotFound);
+        assert_eq!(reply["id"], 4);
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":8,"method":"server.bannr","params":[]}"#,
+        );
+        assert_eq!(error_code(&reply), RpcErrorCode::MethodNotFound);
+
+        // server extension
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":7,"method":"server.echo","params":{"a":1}}"#,
+        );
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"id":7,"jsonrpc":"2.0","result":{"a":1}}"#
+        );
+
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":5,"method":"blockchain.block.header","params":["a"]}"#,
+        );
+        assert_eq!(error_code(&reply), RpcErrorCode::InvalidParams);
+
+        // notifications are not replied
+        let notification = br#"{"jsonrpc":"2.0","method":"server.ping"}"#;
+        assert!(session.handle_line(&Backend, notification).is_none());
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":{},"method":"server.ping"}"#,
+        );
+        assert_eq!(error_code(&reply), RpcErrorCode::InvalidRequest);
+
+        // not implemented by the backend
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":6,"method":"server.features","params":[]}"#,
+        );
+        assert_eq!(error_code(&reply), RpcErrorCode::BadRequest);
+        assert_eq!(reply["id"], 6);
+
+        // string ids are echoed back
+        let reply = handle(
+            &session,
+            r#"{"jsonrpc":"2.0","id":"a-1","method":"server.banner","params":[]}"#,
+        );
+        assert_eq!(reply["id"], "a-1");
+        let reply = handle(&session, r#"{"jsonrpc":"2.0","id":"a-2","method":"toto"}"#);
+        assert_eq!(reply["id"], "a-2");
+
+        let reply = handle(&session, "[]");
+        assert_eq!(error_code(&reply), RpcErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn batch() {
+        let session = Session::new(None);
+        let reply = handle(
+            &session,
+            r#"[{"jsonrpc":"2.0","id":0,"method":"server.ping"},{"jsonrpc":"2.0","id":1,"method":"blockchain.block.header","params":[1]},{"jsonrpc":"2.0","id":2,"method":"blockchain.block.header","params":[0]}]"#,
+        );
+        let reply = reply.as_array().unwrap();
+        assert_eq!(reply.len(), 3);
+        assert_eq!(reply[0]["result"], Value::Null);
+        assert_eq!(error_code(&reply[1]), RpcErrorCode::BadRequest);
+        assert_eq!(
+            reply[2]["result"].as_str().unwrap(),
+            serialize_hex(&genesis_block(Network::Regtest).header)
+        );
+
+        let reply = handle(
+            &session,
+            r#"[{"jsonrpc":"2.0","method":"server.ping"},{"jsonrpc":"2.0","id":1,"method":"server.ping"}]"#,
+        );
+        assert_eq!(reply.as_array().unwrap().len(), 1);
+        assert_eq!(reply[0]["id"], 1);
+        let notifications = br#"[{"jsonrpc":"2.0","method":"server.ping"}]"#;
+        assert!(session.handle_line(&Backend, notifications).is_none());
+    }
+
+    #[test]
+    fn subscriptions() {
+        let session = Session::new(None);
+        let sh = ScriptHash::from_str(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+
+        let response = session
+            .dispatch(&Backend, &Request::subscribe_sh(sh).id(1))
+            .unwrap();
+        let expected = StatusHash::from_history(&Backend.history(&sh).unwrap());
+        match response {
+            Response::SHSubscribe(r) => assert_eq!(r.status_hash().unwrap(), expected),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(
+            session.subscriptions().script_hashes.get(&sh),
+            Some(&expected)
+        );
+
+        session
+            .dispatch(&Backend, &Request::subscribe_headers())
+            .unwrap();
+        assert!(session.subscriptions().headers);
+
+        let response = session
+            .dispatch(&Backend, &Request::unsubscribe_sh(sh))
+            .unwrap();
+        assert_eq!(
+            response,
+            Response::SHUnsubscribe(SHUnsubscribeResponse {
+                id: Id::Number(0),
+                result: true
+            })
+        );
+        assert!(session.subscriptions().script_hashes.is_empty());
+
+        let response = session
+            .dispatch(&Backend, &Request::sh_list_unspent(sh))
+            .unwrap();
+        match response {
+            Response::SHListUnspent(r) => assert_eq!(r.unspent[0].vout, 1),
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+}
@@ -14,7 +14,7 @@ use electrsd::{
 use miniscript::bitcoin::{hex::FromHex, OutPoint, Script};
 use serde_json::Value;
 use simple_electrum_client::{
-    electrum::{request::Request, response::*},
+    electrum::{request::Request, response::*, types::Id},
     raw_client::Client,
 };
 
@@ -49,6 +49,7 @@ fn tcp_client() -> (Client, ElectrsD, BitcoinD) {
     (c, electrs, bitcoind)
 }
 
+#[allow(clippy::manual_ok_err)]
 fn env_var(arg: &str) -> Option<String> {
     if let Ok(value) = env::var(arg) {
         Some(value)
@@ -75,6 +76,15 @@ fn acinq_client() -> Client {
     Client::new().ssl(&url, port)
 }
 
+fn recv(client: &mut Client, index: &HashMap<Id, Request>) -> Vec<Response> {
+    client
+        .recv(index)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect()
+}
+
 #[test]
 fn ping() {
     let (mut client, _electrs, _bitcoind) = tcp_client();
@@ -99,9 +109,9 @@ fn ping_request() {
     let request = Request::ping();
     client.send(&request);
     let mut index = HashMap::new();
-    index.insert(request.id, request);
+    index.insert(request.id.clone(), request);
 
-    let response = &client.recv(&index).unwrap()[0];
+    let response = &recv(&mut client, &index)[0];
 
     if let Response::Ping(_) = response {
         //
@@ -220,10 +230,13 @@ fn banner() {
     let (mut client, _e, _b) = tcp_client();
     client.send(&request);
     let mut index = HashMap::new();
-    index.insert(request.id, request);
+    index.insert(request.id.clone(), request);
     matches!(
-        client.recv(&index).unwrap()[0],
-        Response::Banner(BannerResponse { id: 0, .. })
+        recv(&mut client, &index)[0],
+        Response::Banner(BannerResponse {
+            id: Id::Number(0),
+            ..
+        })
     );
 }
 
@@ -244,10 +257,10 @@ fn block_header() {
     let request = Request::subscribe_headers();
     client.send(&request);
     let mut index = HashMap::new();
-    index.insert(request.id, request.clone());
+    index.insert(request.id.clone(), request.clone());
 
     // We get the chain tip w/ a Request.subscribe_header()
-    let mut responses = client.recv(&index).unwrap();
+    let mut responses = recv(&mut client, &index);
     // NOTE: there is no method to unsubscribe so during the test
     // we can receive an unintended header notification
 
@@ -272,7 +285,10 @@ fn block_header() {
                             // NOTE: we convert it into a HeaderNotification single
                             // TODO: maybe implement a method for fetch id from requests index?
                             response = Response::HeaderNotif(HeaderNotification::Single(
-                                SingleHeaderNotif { id: 0, header },
+                                SingleHeaderNotif {
+                                    id: Id::Number(0),
+                                    header,
+                                },
                             ));
                             break 'l;
                         }
@@ -284,7 +300,7 @@ fn block_header() {
             }
         }
         thread::sleep(Duration::from_millis(100));
-        responses = client.recv(&index).unwrap();
+        responses = recv(&mut client, &index);
     }
 
     if let Response::HeaderNotif(HeaderNotification::Single(SingleHeaderNotif {
@@ -297,8 +313,8 @@ fn block_header() {
         // We can now not get a single header
         let request = Request::header(height);
         client.send(&request);
-        index.insert(request.id, request);
-        let mut responses = client.recv(&index).unwrap();
+        index.insert(request.id.clone(), request);
+        let mut responses = recv(&mut client, &index);
         // NOTE: here we can receive some HeaderNotification::Batch at any time
         // so we need filter out them
         let response: Response;
@@ -313,7 +329,7 @@ fn block_header() {
                     _ => panic!(" wrong response"),
                 }
             }
-            responses = client.recv(&index).unwrap();
+            responses = recv(&mut client, &index);
         }
 
         if let Response::Header(HeaderResponse { id, raw_header }) = response {
@@ -326,8 +342,8 @@ fn block_header() {
         // Now get several headers
         let request = Request::headers(height, 5);
         client.send(&request);
-        index.insert(request.id, request);
-        let response = client.recv(&index).unwrap();
+        index.insert(request.id.clone(), request);
+        let response = recv(&mut client, &index);
         // TODO: handle unintended notification
         if let Response::Headers(HeadersResponse {
             id,
@@ -358,10 +374,10 @@ fn test_version() {
     let request = Request::version("smart".into(), "1.4".into());
     client.send(&request);
     let mut index = HashMap::new();
-    index.insert(request.id, request.clone());
-    let response = client.recv(&index).unwrap();
-    if let Response::Version(VersionResponse { id, .. }) = response[0] {
-        assert_eq!(id, 0);
+    index.insert(request.id.clone(), request.clone());
+    let response = recv(&mut client, &index);
+    if let Response::Version(VersionResponse { id, .. }) = &response[0] {
+        assert_eq!(*id, 0);
     } else {
         panic!("wrong response");
     }
@@ -380,8 +396,8 @@ fn tx_get() {
     let request = Request::tx_get(outpoint.txid);
     client.send(&request);
     let mut index = HashMap::new();
-    index.insert(request.id, request.clone());
-    let response = client.recv(&index).unwrap();
+    index.insert(request.id.clone(), request.clone());
+    let response = recv(&mut client, &index);
     if let Response::TxGet(TxGetResponse {
         id,
         result: TxGetResult::Raw(raw_tx),
@@ -397,8 +413,8 @@ fn tx_get() {
     let request = Request::tx_get_verbose(outpoint.txid);
     client.send(&request);
     let mut index = HashMap::new();
-    index.insert(request.id, request.clone());
-    let response = client.recv(&index).unwrap();
+    index.insert(request.id.clone(), request.clone());
+    let response = recv(&mut client, &index);
     if let Response::TxGet(TxGetResponse {
         id,
         result: TxGetResult::Verbose(tx),
@@ -424,8 +440,8 @@ fn tx_get() {
 //     // unsubscribe w/o subscription we expect result==false
 //     let request = Request::unsubscribe_sh(script);
 //     client.send(&request);
-//     index.insert(request.id, request);
-//     let response = &client.recv(&index).unwrap()[0];
+//     index.insert(request.id.clone(), request);
+//     let response = &recv(&mut client, &index)[0];
 //     if let Response::SHUnsubscribe(SHUnsubscribeResponse { id, result }) = response {
 //         assert_eq!(*id, 0);
 //         assert!(!(*result));
@@ -436,8 +452,8 @@ fn tx_get() {
 //     // subscribe
 //     let request = Request::subscribe_sh(script).id(1);
 //     client.send(&request);
-//     index.insert(request.id, request);
-//     let response = &client.recv(&index).unwrap()[0];
+//     index.insert(request.id.clone(), request);
+//     let response = &recv(&mut client, &index)[0];
 //     if let Response::SHSubscribe(SHSubscribeResponse { id, result }) = response {
 //         assert_eq!(*id, 1);
 //         assert_eq!(*result, None);
@@ -448,8 +464,8 @@ fn tx_get() {
 //     // unsubscribe w/ subscription we expect result==true
 //     let request = Request::unsubscribe_sh(script).id(2);
 //     client.send(&request);
-//     index.insert(request.id, request);
-//     let response = &client.recv(&index).unwrap()[0];
+//     index.insert(request.id.clone(), request);
+//     let response = &recv(&mut client, &index)[0];
 //     if let Response::SHUnsubscribe(SHUnsubscribeResponse { id, result }) = response {
 //         assert_eq!(*id, 2);
 //         assert!((*result));
@@ -470,8 +486,8 @@ fn sh_get_balance() {
     let mut index = HashMap::new();
     let request = Request::sh_get_balance(script);
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::SHGetBalance(_) = response {
     } else {
         panic!("wrong response")
@@ -490,8 +506,8 @@ fn sh_get_history() {
     let mut index = HashMap::new();
     let request = Request::sh_get_history(script);
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::SHGetHistory(_) = response {
     } else {
         panic!("wrong response")
@@ -509,9 +525,9 @@ fn sh_get_history() {
 //     let mut index = HashMap::new();
 //     let request = Request::sh_get_mempool(script);
 //     client.send(&request);
-//     index.insert(request.id, request);
+//     index.insert(request.id.clone(), request);
 //     println!("get_mempool: {}", client.recv_str().unwrap());
-//     // let response = client.recv(&index).unwrap()[0];
+//     // let response = recv(&mut client, &index)[0];
 // }
 
 #[test]
@@ -526,8 +542,8 @@ fn sh_list_unspent() {
     let mut index = HashMap::new();
     let request = Request::sh_list_unspent(script);
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::SHListUnspent(_) = response {
     } else {
         panic!("wrong response")
@@ -541,8 +557,8 @@ fn features() {
     let mut index = HashMap::new();
     let request = Request::features();
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::Features(_) = response {
         //
     } else {
@@ -557,8 +573,8 @@ fn donation() {
     let mut index = HashMap::new();
     let request = Request::donation();
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::Donation(_) = response {
         //
     } else {
@@ -574,8 +590,8 @@ fn estimate_fee() {
     let mut index = HashMap::new();
     let request = Request::estimate_fee(10);
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::EstimateFee(_) = response {
         //
     } else {
@@ -591,8 +607,8 @@ fn fee_histogram() {
     let mut index = HashMap::new();
     let request = Request::get_fee_histogram();
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::FeeHistogram(_) = response {
         //
     } else {
@@ -608,9 +624,9 @@ fn relay_fee() {
     let mut index = HashMap::new();
     let request = Request::relay_fee();
     client.send(&request);
-    index.insert(request.id, request);
+    index.insert(request.id.clone(), request);
     // println!("{}", client.recv_str().unwrap());
-    let response = &client.recv(&index).unwrap()[0];
+    let response = &recv(&mut client, &index)[0];
     if let Response::RelayFee(_) = response {
         //
     } else {
@@ -630,9 +646,9 @@ fn tx_get_merkle() {
     let mut index = HashMap::new();
     let request = Request::tx_get_merkle(outpoint.txid, 200_000);
     client.send(&request);
-    index.insert(request.id, request);
+    index.insert(request.id.clone(), request);
     // println!("{}", client.recv_str().unwrap());
-    let response = &client.recv(&index).unwrap()[0];
+    let response = &recv(&mut client, &index)[0];
     if let Response::TxGetMerkle(_) = response {
         //
     } else {
@@ -649,8 +665,8 @@ fn tx_from_position() {
     let mut index = HashMap::new();
     let request = Request::tx_from_pos(200_000, 3, false);
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::TxFromposition(_) = response {
         //
     } else {
@@ -659,8 +675,8 @@ fn tx_from_position() {
 
     let request = Request::tx_from_pos(300_000, 125, true).id(1);
     client.send(&request);
-    index.insert(request.id, request);
-    let response = &client.recv(&index).unwrap()[0];
+    index.insert(request.id.clone(), request);
+    let response = &recv(&mut client, &index)[0];
     if let Response::TxFromposition(_) = response {
         //
     } else {
@@ -0,0 +1,695 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use miniscript::bitcoin::{
+    absolute::LockTime,
+    block::{Header as BlockHeader, Version},
+    consensus::encode::serialize_hex,
+    constants::genesis_block,
+    hashes::Hash,
+    merkle_tree, transaction, Address, Amount, BlockHash, CompactTarget, Network, ScriptBuf,
+    SignedAmount, Transaction, TxIn, TxMerkleNode, TxOut, Txid,
+};
+use openssl::{
+    asn1::Asn1Time,
+    hash::MessageDigest,
+    pkey::PKey,
+    rsa::Rsa,
+    ssl::{SslAcceptor, SslMethod},
+    x509::{X509Name, X509},
+};
+use simple_electrum_client::{
+    client::{Client, Error as ClientError},
+    electrum::{
+        self,
+        call::{GetBalance, GetBanner, GetHeaders, GetMempool, ListUnspent, Ping},
+        capabilities::{Capabilities, ServerSoftware},
+        method::Method,
+        network,
+        params::VersionKind,
+        request::Request,
+        response::*,
+        script::ToScriptHash,
+        types::{ScriptHash, StatusHash},
+        version::ProtocolVersion,
+    },
+    raw_client::Client as RawClient,
+    server::{BackendResult, ElectrumBackend, Server, ServerHandle, MAX_LINE_SIZE},
+};
+
+fn txids(height: usize) -> Vec<Txid> {
+    (0..3)
+        .map(|i| Txid::from_byte_array([(height * 3 + i) as u8; 32]))
+        .collect()
+}
+
+fn regtest_address() -> Address {
+    Address::p2wsh(&ScriptBuf::from_bytes(vec![0x51]), Network::Regtest)
+}
+
+fn funding_tx() -> Transaction {
+    Transaction {
+        version: transaction::Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn::default()],
+        output: vec![TxOut {
+            value: Amount::from_sat(1000),
+            script_pubkey: regtest_address().script_pubkey(),
+        }],
+    }
+}
+
+fn mine(prev: BlockHash, txids: &[Txid]) -> BlockHeader {
+    let mut header = BlockHeader {
+        version: Version::TWO,
+        prev_blockhash: prev,
+        merkle_root: merkle_tree::calculate_root(
+            txids
+                .iter()
+                .map(|txid| TxMerkleNode::from_raw_hash(txid.to_raw_hash())),
+        )
+        .unwrap(),
+        time: 0,
+        bits: CompactTarget::from_consensus(0x207fffff),
+        nonce: 0,
+    };
+    while header.validate_pow(header.target()).is_err() {
+        header.nonce += 1;
+    }
+    header
+}
+
+#[derive(Default)]
+struct State {
+    headers: Vec<BlockHeader>,
+    history: HashMap<ScriptHash, Vec<HistoryResult>>,
+}
+
+impl State {
+    fn mine(&mut self) {
+        let prev = self
+            .headers
+            .last()
+            .map(|h| h.block_hash())
+            .unwrap_or(BlockHash::all_zeros());
+        let header = mine(prev, &txids(self.headers.len()));
+        self.headers.push(header);
+    }
+}
+
+#[derive(Default)]
+struct Fixture {
+    state: Mutex<State>,
+}
+
+impl Fixture {
+    fn new(height: usize) -> Self {
+        let mut state = State::default();
+        for _ in 0..=height {
+            state.mine();
+        }
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+}
+
+impl ElectrumBackend for Fixture {
+    fn banner(&self) -> BackendResult<String> {
+        Ok("fixture".into())
+    }
+
+    fn donation_address(&self) -> BackendResult<Option<String>> {
+        Ok(Some(regtest_address().to_string()))
+    }
+
+    fn verbose_transaction(&self, txid: &Txid) -> BackendResult<VerboseTx> {
+        let tx = funding_tx();
+        if *txid != tx.compute_txid() {
+            return Err(ErrorResult::bad_request("unknown transaction"));
+        }
+        let vout = tx
+            .output
+            .iter()
+            .enumerate()
+            .map(|(n, out)| VerboseTxOut {
+                value: out.value,
+                n: n as u32,
+                script_pubkey: VerboseScriptPubKey {
+                    asm: String::new(),
+                    desc: None,
+                    hex: out.script_pubkey.clone(),
+                    script_type: ScriptType::P2wsh,
+                    address: Some(regtest_address().as_unchecked().clone()),
+                },
+            })
+            .collect();
+        Ok(VerboseTx {
+            blockhash: None,
+            blocktime: None,
+            confirmations: None,
+            locktime: 0,
+            size: 0,
+            vsize: None,
+            weight: None,
+            time: None,
+            version: 2,
+            txid: *txid,
+            hash: None,
+            raw_tx: serialize_hex(&tx),
+            vin: Vec::new(),
+            vout,
+        })
+    }
+
+    fn features(&self) -> BackendResult<FeaturesResult> {
+        Ok(FeaturesResult {
+            genesis: network::genesis_hash(Network::Regtest).to_string(),
+            hosts: Hosts::Map(HashMap::new()),
+            protocol_max: "1.6".into(),
+            protocol_min: "1.4".into(),
+            pruning: None,
+            server_version: "fixture 0.1".into(),
+            hash_function: "sha256".into(),
+            services: None,
+        })
+    }
+
+    fn tip(&self) -> BackendResult<(usize, BlockHeader)> {
+        let state = self.state.lock().unwrap();
+        Ok((state.headers.len() - 1, *state.headers.last().unwrap()))
+    }
+
+    fn block_header(&self, height: usize) -> BackendResult<BlockHeader> {
+        let state = self.state.lock().unwrap();
+        state
+            .headers
+            .get(height)
+            .copied()
+            .ok_or(ErrorResult::bad_request("height out of range"))
+    }
+
+    fn block_txids(&self, height: usize) -> BackendResult<Vec<Txid>> {
+        self.block_header(height)?;
+        Ok(txids(height))
+    }
+
+    fn balance(&self, script_hash: &ScriptHash) -> BackendResult<BalanceResult> {
+        let (confirmed, unconfirmed): (Vec<_>, Vec<_>) = self
+            .history(script_hash)?
+            .into_iter()
+            .partition(|h| h.is_confirmed());
+        Ok(BalanceResult {
+            confirmed: SignedAmount::from_sat(confirmed.len() as i64 * 1000),
+            unconfirmed: SignedAmount::from_sat(unconfirmed.len() as i64 * 1000),
+        })
+    }
+
+    fn history(&self, script_hash: &ScriptHash) -> BackendResult<Vec<HistoryResult>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.history.get(script_hash).cloned().unwrap_or_default())
+    }
+
+    fn list_unspent(&self, script_hash: &ScriptHash) -> BackendResult<Vec<UtxoResult>> {
+        Ok(self
+            .history(script_hash)?
+            .into_iter()
+            .map(|h| UtxoResult {
+                height: h.height,
+                txid: h.txid,
+                vout: 0,
+                value: Amount::from_sat(1000),
+            })
+            .collect())
+    }
+}
+
+fn receive(fixture: &Fixture, script_hash: ScriptHash, height: usize) {
+    let mut state = fixture.state.lock().unwrap();
+    state
+        .history
+        .entry(script_hash)
+        .or_default()
+        .push(HistoryResult {
+            height: TxHeight::Confirmed(height),
+            txid: txids(height)[1],
+            fee: None,
+        });
+}
+
+fn receive_unconfirmed(fixture: &Fixture, script_hash: ScriptHash) -> Txid {
+    let txid = Txid::from_byte_array([0xff; 32]);
+    let mut state = fixture.state.lock().unwrap();
+    state
+        .history
+        .entry(script_hash)
+        .or_default()
+        .push(HistoryResult {
+            height: TxHeight::Mempool,
+            txid,
+            fee: Some(Amount::from_sat(200)),
+        });
+    txid
+}
+
+fn server(height: usize) -> ServerHandle<Fixture> {
+    Server::new(Fixture::new(height))
+        .bind("127.0.0.1:0")
+        .unwrap()
+}
+
+fn client(server: &ServerHandle<Fixture>) -> Client {
+    let port = server.local_addr().port();
+    let raw = RawClient::new_tcp("127.0.0.1", port).read_timeout(Some(Duration::from_secs(5)));
+    let mut client = Client::new(raw);
+    client.connect().unwrap();
+    client
+}
+
+fn script_hash() -> ScriptHash {
+    ScriptBuf::from_bytes(vec![0x51]).to_script_hash()
+}
+
+#[test]
+fn requests() {
+    let server = server(10);
+    let mut client = client(&server);
+
+    let capabilities = client
+        .negotiate("test", ProtocolVersion::V1_4, ProtocolVersion::V1_6)
+        .unwrap();
+    assert_eq!(capabilities.protocol, ProtocolVersion::V1_6);
+
+    match client.request(Request::banner()).unwrap() {
+        Response::Banner(r) => assert_eq!(r.result, "fixture"),
+        r => panic!("unexpected response {:?}", r),
+    }
+    assert_eq!(client.call(GetBanner).unwrap(), "fixture");
+
+    assert_eq!(client.header(4).unwrap(), expected_header(&server, 4));
+
+    match client.request(Request::headers(8, 10)).unwrap() {
+        Response::Headers(r) => {
+            assert_eq!(r.headers.count, 3);
+            assert_eq!(r.headers.max, 2016);
+            let headers = r.headers.headers().unwrap();
+            assert_eq!(headers[0], expected_header(&server, 8));
+            assert_eq!(headers[2], expected_header(&server, 10));
+        }
+        r => panic!("unexpected response {:?}", r),
+    }
+    let headers = client
+        .call(GetHeaders {
+            start: 8,
+            count: 10,
+        })
+        .unwrap();
+    assert_eq!(headers.count, 3);
+    assert_eq!(headers.max, 2016);
+    let headers = headers.headers().unwrap();
+    assert_eq!(headers[0], expected_header(&server, 8));
+    assert_eq!(headers[2], expected_header(&server, 10));
+
+    match client.request(Request::header(11)).unwrap() {
+        Response::Error(e) => {
+            assert_eq!(e.error.code, RpcErrorCode::BadRequest);
+            assert_eq!(e.method, Some(Method::BlockHeader));
+        }
+        r => panic!("unexpected response {:?}", r),
+    }
+
+    // merkle proofs computed from the block txids
+    let txid = txids(5)[2];
+    assert_eq!(
+        client.verify_tx(txid, 5, None).unwrap(),
+        expected_header(&server, 5)
+    );
+    assert_eq!(client.block_txids(5, None).unwrap(), txids(5));
+
+    let sh = script_hash();
+    receive(server.backend(), sh, 3);
+    receive(server.backend(), sh, 7);
+    let responses = client
+        .batch(vec![
+            Request::sh_get_balance(sh),
+            Request::sh_list_unspent(sh),
+            Request::ping(),
+        ])
+        .unwrap();
+    match &responses[..] {
+        [Response::SHGetBalance(balance), Response::SHListUnspent(unspent), Response::Ping(_)] => {
+            assert_eq!(balance.balance.total(), SignedAmount::from_sat(2000));
+            assert_eq!(unspent.unspent.len(), 2);
+        }
+        r => panic!("unexpected responses {:?}", r),
+    }
+    let (balance, unspent, ()) = client
+        .call_batch((GetBalance(sh), ListUnspent(sh), Ping))
+        .unwrap();
+    assert_eq!(balance.total(), SignedAmount::from_sat(2000));
+    assert_eq!(unspent.len(), 2);
+    let balances = client.call_batch(vec![GetBalance(sh); 3]).unwrap();
+    assert_eq!(balances.len(), 3);
+    assert!(client.mempool(sh).unwrap().is_empty());
+    let txid = receive_unconfirmed(server.backend(), sh);
+    // through get_mempool
+    let mempool = client.mempool(sh).unwrap();
+    assert_eq!(mempool.len(), 1);
+    assert_eq!(mempool[0].txid, txid);
+    assert_eq!(mempool[0].fee, Some(Amount::from_sat(200)));
+    let capabilities = client.capabilities().cloned();
+    client.set_capabilities(Some(Capabilities::new(
+        ProtocolVersion::V1_4,
+        ServerSoftware::Electrs,
+    )));
+    // falls back to get_history
+    assert!(client.call(GetMempool(sh)).is_err());
+    assert_eq!(client.mempool(sh).unwrap(), mempool);
+    client.set_capabilities(capabilities);
+
+    let status = StatusHash::from_history(&server.backend().history(&sh).unwrap());
+    assert_eq!(client.history(sh, status).unwrap().len(), 3);
+
+    let results = client
+        .batch_results(vec![Request::header(99), Request::banner()])
+        .unwrap();
+    assert!(matches!(&results[0], Ok(Response::Error(e)) if e.id.is_some()));
+    assert!(matches!(&results[1], Ok(Response::Banner(_))));
+}
+
+fn expected_header(server: &ServerHandle<Fixture>, height: usize) -> BlockHeader {
+    server.backend().block_header(height).unwrap()
+}
+
+#[test]
+fn notifications() {
+    let server = server(5);
+    let mut client = client(&server);
+    let sh = script_hash();
+
+    match client.request(Request::subscribe_sh(sh)).unwrap() {
+        Response::SHSubscribe(r) => assert_eq!(r.status_hash().unwrap(), None),
+        r => panic!("unexpected response {:?}", r),
+    }
+    match client.request(Request::subscribe_headers()).unwrap() {
+        Response::HeaderNotif(HeaderNotification::Single(r)) => assert_eq!(r.header.height, 5),
+        r => panic!("unexpected response {:?}", r),
+    }
+
+    // status did not change
+    server.notify_script_hash(&sh).unwrap();
+    receive(server.backend(), sh, 2);
+    server.notify_script_hash(&sh).unwrap();
+    let notification = client.recv().unwrap().pop().unwrap();
+    let expected = StatusHash::from_history(&server.backend().history(&sh).unwrap());
+    match notification {
+        Response::SHNotification(n) => {
+            assert_eq!(n.script_hash(), sh);
+            assert_eq!(n.status_hash().unwrap(), expected);
+        }
+        r => panic!("unexpected notification {:?}", r),
+    }
+
+    server.backend().state.lock().unwrap().mine();
+    server.notify_tip().unwrap();
+    match client.recv().unwrap().pop().unwrap() {
+        Response::BatchHeaderNotif(n) => {
+            assert_eq!(n.headers[0].height, 6);
+            assert_eq!(n.headers[0].header().unwrap(), expected_header(&server, 6));
+        }
+        r => panic!("unexpected notification {:?}", r),
+    }
+
+    match client.request(Request::unsubscribe_sh(sh)).unwrap() {
+        Response::SHUnsubscribe(r) => assert!(r.result),
+        r => panic!("unexpected response {:?}", r),
+    }
+}
+
+fn self_signed_acceptor() -> SslAcceptor {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509Name::builder().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+    let mut cert = X509::builder().unwrap();
+    cert.set_version(2).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+    let cert = cert.build();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.build()
+}
+
+#[test]
+fn ssl() {
+    let server = Server::new(Fixture::new(2))
+        .ssl(self_signed_acceptor())
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let raw = RawClient::new_ssl("localhost", server.local_addr().port())
+        .verif_certificate(false)
+        .read_timeout(Some(Duration::from_secs(5)));
+    let mut client = Client::new(raw);
+    client.connect().unwrap();
+
+    match client.request(Request::ping()).unwrap() {
+        Response::Ping(_) => {}
+        r => panic!("unexpected response {:?}", r),
+    }
+    assert_eq!(client.header(2).unwrap(), expected_header(&server, 2));
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn network() {
+    let server = server(0);
+    let port = server.local_addr().port();
+    let connect = |network| {
+        let raw = RawClient::new_tcp("127.0.0.1", port).read_timeout(Some(Duration::from_secs(5)));
+        let mut client = Client::new(raw).network(network);
+        // NOTE: the network is only checked after `server.version`
+        client.connect().unwrap();
+        client
+            .negotiate("test", ProtocolVersion::V1_4, ProtocolVersion::V1_6)
+            .map(|_| ())
+    };
+    connect(Network::Regtest).unwrap();
+    assert!(matches!(
+        connect(Network::Bitcoin),
+        Err(ClientError::Electrum(electrum::Error::WrongNetwork {
+            expected: Network::Bitcoin,
+            ..
+        }))
+    ));
+}
+
+/// A backend w/o `server.features`, serving the regtest genesis block.
+struct NoFeatures(&'static str);
+
+impl ElectrumBackend for NoFeatures {
+    fn version(
+        &self,
+        _client_name: &str,
+        _protocol: &VersionKind,
+    ) -> BackendResult<(String, ProtocolVersion)> {
+        Ok((self.0.into(), ProtocolVersion::V1_4))
+    }
+
+    fn block_header(&self, height: usize) -> BackendResult<BlockHeader> {
+        match height {
+            0 => Ok(genesis_block(Network::Regtest).header),
+            _ => Err(ErrorResult::bad_request("height out of range")),
+        }
+    }
+}
+
+#[test]
+fn network_wo_features() {
+    // `server.features` is gated for electrs 0.8 & fails on the esplora fork
+    for name in ["electrs/0.8.12", "electrs-esplora 0.4.1"] {
+        let server = Server::new(NoFeatures(name)).bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().port();
+        let connect = |network| {
+            let raw =
+                RawClient::new_tcp("127.0.0.1", port).read_timeout(Some(Duration::from_secs(5)));
+            let mut client = Client::new(raw).network(network);
+            client.connect().unwrap();
+            client
+                .negotiate("test", ProtocolVersion::V1_4, ProtocolVersion::V1_4)
+                .map(|c| c.software.clone())
+        };
+        assert_eq!(
+            connect(Network::Regtest).unwrap(),
+            ServerSoftware::parse(name)
+        );
+        assert!(matches!(
+            connect(Network::Bitcoin),
+            Err(ClientError::Electrum(electrum::Error::WrongNetwork {
+                expected: Network::Bitcoin,
+                ..
+            }))
+        ));
+    }
+}
+
+#[test]
+fn addresses() {
+    let server = server(0);
+    let mut client = client(&server);
+    let txid = funding_tx().compute_txid();
+
+    // the network must be known to check the donation address
+    assert!(matches!(
+        client.donation_address(),
+        Err(ClientError::NetworkNotSet)
+    ));
+    // w/o network the addresses of verbose txs are not checked
+    assert_eq!(client.verbose_transaction(txid).unwrap().vout.len(), 1);
+
+    client.set_network(Some(Network::Regtest));
+    assert_eq!(client.donation_address().unwrap(), Some(regtest_address()));
+    let tx = client.verbose_transaction(txid).unwrap();
+    assert_eq!(
+        tx.vout[0]
+            .script_pubkey
+            .checked_address(Network::Regtest)
+            .unwrap(),
+        Some(regtest_address())
+    );
+
+    client.set_network(Some(Network::Bitcoin));
+    assert!(matches!(
+        client.donation_address(),
+        Err(ClientError::Electrum(electrum::Error::InvalidAddress(_)))
+    ));
+    assert!(matches!(
+        client.verbose_transaction(txid),
+        Err(ClientError::Electrum(electrum::Error::InvalidAddress(_)))
+    ));
+}
+
+#[test]
+fn line_too_long() {
+    use std::io::{Read, Write};
+
+    let server = server(0);
+    let mut stream = std::net::TcpStream::connect(server.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":0,\"method\":\"server.ping\"}\n")
+        .unwrap();
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !reply.ends_with(b"\n") {
+        let n = stream.read(&mut buf).unwrap();
+        assert!(n > 0);
+        reply.extend_from_slice(&buf[..n]);
+    }
+
+    // NOTE: the server may close before all is written
+    let _ = stream.write_all(&vec![b' '; MAX_LINE_SIZE + 1]);
+    assert!(matches!(stream.read(&mut buf), Ok(0) | Err(_)));
+}
+
+#[test]
+fn headers_overflow() {
+    let server = server(2);
+    let mut client = client(&server);
+    match client.request(Request::headers(usize::MAX, 10)).unwrap() {
+        Response::Headers(r) => assert_eq!(r.headers.count, 0),
+        r => panic!("unexpected response {:?}", r),
+    }
+    match client
+        .request(Request::headers_with_checkpoint(usize::MAX, 10, 5))
+        .unwrap()
+    {
+        Response::Error(e) => assert_eq!(e.error.code, RpcErrorCode::InvalidParams),
+        r => panic!("unexpected response {:?}", r),
+    }
+    assert_eq!(server.connections(), 1);
+}
+
+struct Panicking;
+
+impl ElectrumBackend for Panicking {
+    fn banner(&self) -> BackendResult<String> {
+        panic!("backend failure")
+    }
+}
+
+fn wait_connections<B: ElectrumBackend>(server: &ServerHandle<B>, expected: usize) {
+    let start = std::time::Instant::now();
+    while server.connections() != expected {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn backend_panic() {
+    let server = Server::new(Panicking).bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().port();
+    let raw = RawClient::new_tcp("127.0.0.1", port).read_timeout(Some(Duration::from_secs(5)));
+    let mut client = Client::new(raw);
+    client.connect().unwrap();
+    assert!(matches!(
+        client.request(Request::ping()).unwrap(),
+        Response::Ping(_)
+    ));
+    wait_connections(&server, 1);
+    // the session is dropped w/ its thread
+    assert!(client.request(Request::banner()).is_err());
+    wait_connections(&server, 0);
+}
+
+#[test]
+fn max_connections() {
+    use std::io::Read;
+
+    let server = Server::new(Fixture::new(0))
+        .max_connections(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let mut first = client(&server);
+    assert!(matches!(
+        first.request(Request::ping()).unwrap(),
+        Response::Ping(_)
+    ));
+
+    let mut stream = std::net::TcpStream::connect(server.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    assert!(matches!(stream.read(&mut [0u8; 16]), Ok(0) | Err(_)));
+
+    // NOTE: the slot is released once the connection thread exits
+    first.close().unwrap();
+    let start = std::time::Instant::now();
+    while client(&server).request(Request::ping()).is_err() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn stop() {
+    let mut server = server(0);
+    let mut client = client(&server);
+    assert!(matches!(
+        client.request(Request::ping()).unwrap(),
+        Response::Ping(_)
+    ));
+    server.stop();
+    assert_eq!(server.connections(), 0);
+    assert!(client.request(Request::ping()).is_err());
+}